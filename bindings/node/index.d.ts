@@ -10,25 +10,70 @@ export interface CookieObject {
   expires?: number
   name: string
   value: string
+  /** Value as stored by the browser, which may not be valid UTF-8 */
+  valueBytes: Buffer
   httpOnly: boolean
-  sameSite: number
+  sameSite: string
+  sameSiteRaw?: number
+  profile?: string
+  creation?: number
+  lastAccessed?: number
+  lastUpdate?: number
+  priority?: number
+  sourceScheme?: number
+  sourcePort?: number
+  schemeMap?: number
+  isPersistent?: boolean
+}
+/** Where the key decrypting chromium cookies comes from */
+export interface ChromiumKeySourceObject {
+  /** `Local State` file (Windows) */
+  keyPath?: string
+  /** Browser name whose keyring password is used (Linux, macOS) */
+  browser?: string
+  /** `Safe Storage` password (Linux, macOS) */
+  password?: string
+  /** AES key */
+  key?: Buffer
+}
+export interface ProfileObject {
+  browser: string
+  channel: string
+  directory: string
+  name?: string
+  email?: string
+  displayName: string
+  isDefault: boolean
+  dbPath: string
+  keyPath?: string
 }
 export declare function version(): string
+/** Forgets the keyring passwords cached since the first extraction */
+export declare function clearKeyCache(): void
+/** Lists profiles of installed browsers */
+export declare function listProfiles(): Array<ProfileObject>
 export declare function anyBrowser(dbPath: string, domains?: Array<string> | undefined | null, keyPath?: string | undefined | null): Array<CookieObject>
 /** Common browsers */
-export declare function firefox(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function librewolf(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function chrome(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function brave(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function arc(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function edge(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function opera(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function operaGx(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function chromium(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function vivaldi(domains?: Array<string> | undefined | null): Array<CookieObject>
+export declare function firefox(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function librewolf(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function chrome(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function brave(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function arc(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function edge(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function opera(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function operaGx(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function chromium(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function vivaldi(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+/**
+ * Extracts cookies from a chromium based browser database
+ *
+ * At most one field of the key source can be set, the keyring password of Chrome is used by
+ * default. The platform (`linux`, `macos` or `windows`) the profile comes from decrypts a copied
+ * profile with its password or key.
+ */
+export declare function chromiumBased(dbPath: string, domains?: Array<string> | undefined | null, keySource?: ChromiumKeySourceObject | undefined | null, platform?: string | undefined | null): Array<CookieObject>
 export declare function firefoxBased(dbPath: string, domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function load(domains?: Array<string> | undefined | null): Array<CookieObject>
+export declare function load(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
 /** Windows only browsers */
-export declare function octoBrowser(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function internetExplorer(domains?: Array<string> | undefined | null): Array<CookieObject>
-export declare function chromiumBased(keyPath: string, dbPath: string, domains?: Array<string> | undefined | null): Array<CookieObject>
+export declare function octoBrowser(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
+export declare function internetExplorer(domains?: Array<string> | undefined | null, profile?: string | number | undefined | null): Array<CookieObject>
@@ -310,9 +310,11 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { version, anyBrowser, firefox, librewolf, chrome, brave, arc, edge, opera, operaGx, chromium, vivaldi, firefoxBased, load, octoBrowser, internetExplorer, chromiumBased } = nativeBinding
+const { version, clearKeyCache, listProfiles, anyBrowser, firefox, librewolf, chrome, brave, arc, edge, opera, operaGx, chromium, vivaldi, firefoxBased, load, octoBrowser, internetExplorer, chromiumBased } = nativeBinding
 
 module.exports.version = version
+module.exports.clearKeyCache = clearKeyCache
+module.exports.listProfiles = listProfiles
 module.exports.anyBrowser = anyBrowser
 module.exports.firefox = firefox
 module.exports.librewolf = librewolf
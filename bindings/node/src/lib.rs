@@ -1,8 +1,10 @@
 #[macro_use]
 extern crate napi_derive;
 
-use napi::{Result, Status};
-use rookie::enums::Cookie;
+use napi::{bindgen_prelude::Buffer, Either, Result};
+use rookie::{
+  enums::Cookie, ChromiumKeySource, ChromiumPlatform, CookieQuery, ProfileSelector, RookieError,
+};
 use std::path::PathBuf;
 
 #[napi(object)]
@@ -13,8 +15,47 @@ pub struct CookieObject {
   pub expires: Option<i64>,
   pub name: String,
   pub value: String,
+  /// Value as stored by the browser, which may not be valid UTF-8
+  pub value_bytes: Buffer,
   pub http_only: bool,
-  pub same_site: i64,
+  pub same_site: String,
+  pub same_site_raw: Option<i64>,
+  pub profile: Option<String>,
+  pub creation: Option<i64>,
+  pub last_accessed: Option<i64>,
+  pub last_update: Option<i64>,
+  pub priority: Option<i64>,
+  pub source_scheme: Option<i64>,
+  pub source_port: Option<i64>,
+  pub scheme_map: Option<i64>,
+  pub is_persistent: Option<bool>,
+}
+
+/// Where the key decrypting chromium cookies comes from
+#[napi(object)]
+#[derive(Default)]
+pub struct ChromiumKeySourceObject {
+  /// `Local State` file (Windows)
+  pub key_path: Option<String>,
+  /// Browser name whose keyring password is used (Linux, macOS)
+  pub browser: Option<String>,
+  /// `Safe Storage` password (Linux, macOS)
+  pub password: Option<String>,
+  /// AES key
+  pub key: Option<Buffer>,
+}
+
+#[napi(object)]
+pub struct ProfileObject {
+  pub browser: String,
+  pub channel: String,
+  pub directory: String,
+  pub name: Option<String>,
+  pub email: Option<String>,
+  pub display_name: String,
+  pub is_default: bool,
+  pub db_path: String,
+  pub key_path: Option<String>,
 }
 
 #[napi]
@@ -22,189 +63,304 @@ pub fn version() -> Result<String> {
   Ok(rookie::version())
 }
 
-fn cookies_to_js(cookies: Vec<Cookie>) -> Result<Vec<CookieObject>> {
+/// Forgets the keyring passwords cached since the first extraction
+#[napi]
+pub fn clear_key_cache() {
+  rookie::keys::clear_key_cache()
+}
+
+/// Converts rookie error to JS error, its kind is available as `error.code`
+fn to_js_error(error: RookieError) -> napi::Error<&'static str> {
+  let code = match error {
+    RookieError::BrowserNotInstalled(_) => "BROWSER_NOT_INSTALLED",
+    RookieError::UnsupportedBrowser(_) => "UNSUPPORTED_BROWSER",
+    RookieError::KeyringLocked(_) => "KEYRING_LOCKED",
+    RookieError::DatabaseLocked { .. } => "DATABASE_LOCKED",
+    RookieError::DecryptionFailed(_) => "DECRYPTION_FAILED",
+    RookieError::UnsupportedSchema(_) => "UNSUPPORTED_SCHEMA",
+    RookieError::PermissionDenied { .. } => "PERMISSION_DENIED",
+    RookieError::Timeout { .. } => "TIMEOUT",
+    RookieError::Cancelled(_) => "CANCELLED",
+    RookieError::Other(_) => "UNKNOWN",
+  };
+  let mut message = error.to_string();
+  let mut source = std::error::Error::source(&error);
+  while let Some(cause) = source {
+    message += &format!("\nCaused by: {}", cause);
+    source = cause.source();
+  }
+  napi::Error::new(code, message)
+}
+
+fn cookies_to_js(cookies: Vec<Cookie>) -> Result<Vec<CookieObject>, &'static str> {
   let mut js_cookies: Vec<CookieObject> = vec![];
   for cookie in cookies {
+    let value_bytes = cookie.raw_value().to_vec().into();
     js_cookies.push(CookieObject {
       domain: cookie.domain,
       path: cookie.path,
       secure: cookie.secure,
       http_only: cookie.http_only,
-      same_site: cookie.same_site,
+      same_site: cookie.same_site.as_str().to_string(),
+      same_site_raw: cookie.same_site_raw,
       expires: cookie.expires.map(|v| v as i64),
       name: cookie.name,
       value: cookie.value,
+      value_bytes,
+      profile: cookie.profile,
+      creation: cookie.creation.map(|v| v as i64),
+      last_accessed: cookie.last_accessed.map(|v| v as i64),
+      last_update: cookie.last_update.map(|v| v as i64),
+      priority: cookie.priority,
+      source_scheme: cookie.source_scheme,
+      source_port: cookie.source_port,
+      scheme_map: cookie.scheme_map,
+      is_persistent: cookie.is_persistent,
     });
   }
 
   Ok(js_cookies)
 }
 
+/// Extracts cookies from a browser by its config name, or from all browsers
+///
+/// Profile is selected by directory, display name or index.
+fn query_cookies(
+  browser: Option<&str>,
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  let mut query = CookieQuery::new();
+  if let Some(browser) = browser {
+    query = query.browser(browser);
+  }
+  if let Some(domains) = domains {
+    query = query.domains(domains);
+  }
+  if let Some(profile) = profile {
+    query = query.profile(match profile {
+      Either::A(name) => ProfileSelector::Name(name),
+      Either::B(index) => ProfileSelector::Index(index as usize),
+    });
+  }
+  let cookies = query.run().map_err(to_js_error)?;
+  cookies_to_js(cookies)
+}
+
+/// Lists profiles of installed browsers
+#[napi]
+pub fn list_profiles() -> Vec<ProfileObject> {
+  rookie::list_profiles()
+    .into_iter()
+    .map(|profile| ProfileObject {
+      display_name: profile.display_name(),
+      browser: profile.browser,
+      channel: profile.channel,
+      directory: profile.directory,
+      name: profile.name,
+      email: profile.email,
+      is_default: profile.is_default,
+      db_path: profile.db_path.to_string_lossy().to_string(),
+      key_path: profile
+        .key_path
+        .map(|path| path.to_string_lossy().to_string()),
+    })
+    .collect()
+}
+
 #[napi]
 pub fn any_browser(
   db_path: String,
   domains: Option<Vec<String>>,
   key_path: Option<&str>,
-) -> Result<Vec<CookieObject>> {
-  let cookies = rookie::any_browser(&db_path, domains, key_path)
-    .map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
+) -> Result<Vec<CookieObject>, &'static str> {
+  let cookies = rookie::any_browser(&db_path, domains, key_path).map_err(to_js_error)?;
   cookies_to_js(cookies)
 }
 
 /// Common browsers
 
 #[napi]
-pub fn firefox(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::firefox(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn firefox(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("firefox"), domains, profile)
 }
 
 #[napi]
-pub fn zen(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::zen(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn zen(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("zen"), domains, profile)
 }
 
 #[napi]
-pub fn librewolf(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::librewolf(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn librewolf(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("librewolf"), domains, profile)
 }
 
 #[napi]
-pub fn chrome(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::chrome(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn chrome(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("chrome"), domains, profile)
 }
 
 #[napi]
-pub fn brave(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::brave(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-
-  cookies_to_js(cookies)
+pub fn brave(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("brave"), domains, profile)
 }
 
 #[napi]
-pub fn arc(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::arc(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-
-  cookies_to_js(cookies)
+pub fn arc(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("arc"), domains, profile)
 }
 
 #[napi]
-pub fn edge(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::edge(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn edge(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("edge"), domains, profile)
 }
 
 #[napi]
-pub fn opera(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::opera(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-
-  cookies_to_js(cookies)
+pub fn opera(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("opera"), domains, profile)
 }
 
 #[napi]
-pub fn opera_gx(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::opera_gx(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-
-  cookies_to_js(cookies)
+pub fn opera_gx(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("opera_gx"), domains, profile)
 }
 
 #[napi]
-pub fn chromium(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::chromium(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn chromium(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("chromium"), domains, profile)
 }
 
 #[napi]
-pub fn vivaldi(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::vivaldi(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
+pub fn vivaldi(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("vivaldi"), domains, profile)
+}
 
+/// Extracts cookies from a chromium based browser database
+///
+/// At most one field of the key source can be set, the keyring password of Chrome is used by
+/// default. The platform (`linux`, `macos` or `windows`) the profile comes from decrypts a copied
+/// profile with its password or key.
+#[napi]
+pub fn chromium_based(
+  db_path: String,
+  domains: Option<Vec<String>>,
+  key_source: Option<ChromiumKeySourceObject>,
+  platform: Option<String>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  let key_source = key_source.unwrap_or_default();
+  let mut sources: Vec<ChromiumKeySource> = [
+    key_source
+      .key_path
+      .map(|key_path| ChromiumKeySource::LocalState(key_path.into())),
+    key_source.browser.map(ChromiumKeySource::Browser),
+    key_source.password.map(ChromiumKeySource::Password),
+    key_source
+      .key
+      .map(|key| ChromiumKeySource::Key(key.to_vec())),
+  ]
+  .into_iter()
+  .flatten()
+  .collect();
+  if sources.len() > 1 {
+    return Err(napi::Error::new(
+      "INVALID_ARG",
+      "only one of keyPath, browser, password and key can be set".to_string(),
+    ));
+  }
+  let key_source = sources
+    .pop()
+    .unwrap_or(ChromiumKeySource::Browser("chrome".to_string()));
+  let db_path = PathBuf::from(db_path);
+  let cookies = match platform {
+    Some(platform) => {
+      let platform = platform
+        .parse::<ChromiumPlatform>()
+        .map_err(|e| napi::Error::new("INVALID_ARG", e.to_string()))?;
+      rookie::chromium_based_offline(platform, key_source, db_path, domains)
+    }
+    None => rookie::chromium_based(key_source, db_path, domains),
+  }
+  .map_err(to_js_error)?;
   cookies_to_js(cookies)
 }
 
 #[napi]
-pub fn firefox_based(db_path: String, domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies = rookie::firefox_based(PathBuf::from(db_path), domains)
-    .map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
+pub fn firefox_based(
+  db_path: String,
+  domains: Option<Vec<String>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  let cookies = rookie::firefox_based(PathBuf::from(db_path), domains).map_err(to_js_error)?;
   cookies_to_js(cookies)
 }
 
 #[napi]
-pub fn load(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::load(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn load(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(None, domains, profile)
 }
 
 /// Windows only browsers
 
 #[napi]
 #[cfg(target_os = "windows")]
-pub fn octo_browser(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies = rookie::octo_browser(domains)
-    .map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-
-  cookies_to_js(cookies)
+pub fn octo_browser(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("octo_browser"), domains, profile)
 }
 
 #[napi]
 #[cfg(target_os = "windows")]
-pub fn internet_explorer(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies = rookie::internet_explorer(domains)
-    .map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
-}
-#[napi]
-#[cfg(target_os = "windows")]
-pub fn chromium_based(
-  key_path: String,
-  db_path: String,
+pub fn internet_explorer(
   domains: Option<Vec<String>>,
-) -> Result<Vec<CookieObject>> {
-  let cookies = rookie::chromium_based(PathBuf::from(key_path), PathBuf::from(db_path), domains)
-    .map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("ie"), domains, profile)
 }
 
 /// MacOS browsers
 
 #[napi]
 #[cfg(target_os = "macos")]
-pub fn safari(domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  let cookies =
-    rookie::safari(domains).map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
-}
-
-/// Unix browsers
-
-#[napi]
-#[cfg(unix)]
-pub fn chromium_based(db_path: String, domains: Option<Vec<String>>) -> Result<Vec<CookieObject>> {
-  use rookie::config::Browser;
-
-  let db_path = db_path.as_str();
-  let config = Browser {
-    channels: None,
-    paths: vec![db_path.to_string()],
-    unix_crypt_name: Some("chrome".to_string()),
-    osx_key_service: None,
-    osx_key_user: None,
-  };
-  let cookies = rookie::chromium_based(&config, PathBuf::from(db_path), domains)
-    .map_err(|e| napi::Error::new(Status::Unknown, format!("{e:?}")))?;
-  cookies_to_js(cookies)
+pub fn safari(
+  domains: Option<Vec<String>>,
+  profile: Option<Either<String, u32>>,
+) -> Result<Vec<CookieObject>, &'static str> {
+  query_cookies(Some("safari"), domains, profile)
 }
@@ -16,7 +16,19 @@ from .rookiepy import (
     librewolf,
     load,
     any_browser,
-    version
+    list_profiles,
+    clear_key_cache,
+    version,
+    RookieError,
+    BrowserNotInstalledError,
+    UnsupportedBrowserError,
+    KeyringLockedError,
+    DatabaseLockedError,
+    DecryptionFailedError,
+    UnsupportedSchemaError,
+    PermissionDeniedError,
+    OperationTimeoutError,
+    CancelledError,
 )
 
 __all__ = [
@@ -36,7 +48,19 @@ __all__ = [
     "to_cookiejar",
     "create_cookie",
     "load",
-    "any_browser"
+    "any_browser",
+    "list_profiles",
+    "clear_key_cache",
+    "RookieError",
+    "BrowserNotInstalledError",
+    "UnsupportedBrowserError",
+    "KeyringLockedError",
+    "DatabaseLockedError",
+    "DecryptionFailedError",
+    "UnsupportedSchemaError",
+    "PermissionDeniedError",
+    "OperationTimeoutError",
+    "CancelledError",
 ]
 
 
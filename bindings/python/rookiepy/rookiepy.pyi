@@ -1,187 +1,323 @@
-from typing import Any, Dict, List, Optional
-from sys import platform
-
-CookieList = List[Dict[str, Any]]
-
-def version() -> str:
-    """
-    Get rookie version
-
-    :return: rookie version
-    """
-    ...
-
-def firefox(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Firefox
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def zen(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Zen
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def firefox_based(
-    key_path: str, db_path: str, domains: Optional[List[str]] = None
-) -> CookieList:
-    """
-    Extract Cookies from Firefox-based browsers
-
-    :param key_path: Path to the key file
-    :param db_path: Path to the database file
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def brave(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Brave browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def edge(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Microsoft Edge browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def chrome(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Google Chrome browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def chromium_based(db_path: str, domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Chromium-based browsers
-
-    :param db_path: Path to the database file
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def chromium(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Chromium browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def arc(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Arc browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def opera(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Opera browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def vivaldi(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Vivaldi browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def opera_gx(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from Opera GX browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def librewolf(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Extract Cookies from LibreWolf browser
-
-    :param domains: Optional list of domains to extract only from them
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def load(domains: Optional[List[str]] = None) -> CookieList:
-    """
-    Load Cookies from a browser
-
-    :param domains: Optional list of domains to load cookies from
-    :return: A list of dictionaries of cookies
-    """
-    ...
-
-def any_browser(
-    db_path: str, domains: Optional[List[str]] = ..., key_path: Optional[str] = ...
-) -> List[Dict[str, str]]:
-    """
-    Extract Cookies from any browser.
-
-    :param db_path: Path to browser database file.
-    :param domains: Optional list of domains to extract cookies only from these domains.
-    :param key_path: Optional path to key file used to decrypt `db_path`.
-    :return: A list of dictionaries of cookies.
-    """
-    ...
-
-# Windows
-if platform == "win32":
-    def internet_explorer(domains: Optional[List[str]] = None) -> CookieList:
-        """
-        Extract Cookies from Internet Explorer
-
-        :param domains: Optional list of domains to extract only from them
-        :return: A list of dictionaries of cookies
-        """
-        ...
-
-    def octo_browser(domains: Optional[List[str]] = None) -> CookieList:
-        """
-        Extract Cookies from Octo browser
-
-        :param domains: Optional list of domains to extract only from them
-        :return: A list of dictionaries of cookies
-        """
-        ...
-
-# MacOS
-if platform == "darwin":
-    def safari(domains: Optional[List[str]] = None) -> CookieList:
-        """
-        Extract Cookies from Safari browser
-
-        :param domains: Optional list of domains to extract only from them
-        :return: A list of dictionaries of cookies
-        """
-        ...
+from typing import Any, Dict, List, Optional, Union
+from sys import platform
+
+CookieList = List[Dict[str, Any]]
+
+class RookieError(Exception):
+    """Base class of rookie errors"""
+
+class BrowserNotInstalledError(RookieError):
+    """Browser is not installed or has no cookies file"""
+
+class UnsupportedBrowserError(RookieError):
+    """Browser is not supported on this platform"""
+
+class KeyringLockedError(RookieError):
+    """Keyring holding the decryption key is locked"""
+
+class DatabaseLockedError(RookieError):
+    """Cookies database is locked by another process"""
+
+class DecryptionFailedError(RookieError):
+    """Cookies can't be decrypted"""
+
+class UnsupportedSchemaError(RookieError):
+    """Cookies file layout is not supported"""
+
+class PermissionDeniedError(RookieError):
+    """Permission denied for cookies file"""
+
+class OperationTimeoutError(RookieError):
+    """Operation didn't finish in time"""
+
+class CancelledError(RookieError):
+    """Operation was cancelled"""
+
+def version() -> str:
+    """
+    Get rookie version
+
+    :return: rookie version
+    """
+    ...
+
+def clear_key_cache() -> None:
+    """
+    Forget the keyring passwords cached since the first extraction
+    """
+    ...
+
+def firefox(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Firefox
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def zen(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Zen
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def firefox_based(
+    key_path: str, db_path: str, domains: Optional[List[str]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Firefox-based browsers
+
+    :param key_path: Path to the key file
+    :param db_path: Path to the database file
+    :param domains: Optional list of domains to extract only from them
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def brave(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Brave browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def edge(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Microsoft Edge browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def chrome(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Google Chrome browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+if platform == "win32":
+    def chromium_based(
+        key_path: Optional[str],
+        db_path: str,
+        domains: Optional[List[str]] = None,
+        *,
+        browser: Optional[str] = None,
+        password: Optional[str] = None,
+        key: Optional[bytes] = None,
+        platform: Optional[str] = None,
+    ) -> CookieList:
+        """
+        Extract Cookies from Chromium-based browsers
+
+        At most one of `key_path`, `browser`, `password` and `key` can be set,
+        the keyring password of Chrome is used by default.
+
+        :param key_path: Path to the `Local State` file, or None to use another key source
+        :param db_path: Path to the database file
+        :param domains: Optional list of domains to extract only from them
+        :param browser: Optional browser name whose keyring password is used (Linux, macOS)
+        :param password: Optional `Safe Storage` password (Linux, macOS)
+        :param key: Optional AES key
+        :param platform: Optional OS the profile comes from (`linux`, `macos` or `windows`), to decrypt
+            a copied profile with its password or key
+        :return: A list of dictionaries of cookies
+        """
+        ...
+else:
+    def chromium_based(
+        db_path: str,
+        domains: Optional[List[str]] = None,
+        *,
+        key_path: Optional[str] = None,
+        browser: Optional[str] = None,
+        password: Optional[str] = None,
+        key: Optional[bytes] = None,
+        platform: Optional[str] = None,
+    ) -> CookieList:
+        """
+        Extract Cookies from Chromium-based browsers
+
+        At most one of `key_path`, `browser`, `password` and `key` can be set,
+        the keyring password of Chrome is used by default.
+
+        :param db_path: Path to the database file
+        :param domains: Optional list of domains to extract only from them
+        :param key_path: Optional path to the `Local State` file of a Windows profile
+        :param browser: Optional browser name whose keyring password is used
+        :param password: Optional `Safe Storage` password
+        :param key: Optional AES key
+        :param platform: Optional OS the profile comes from (`linux`, `macos` or `windows`), to decrypt
+            a copied profile with its password or key
+        :return: A list of dictionaries of cookies
+        """
+        ...
+
+def chromium(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Chromium browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def arc(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Arc browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def opera(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Opera browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def vivaldi(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Vivaldi browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def opera_gx(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from Opera GX browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def librewolf(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Extract Cookies from LibreWolf browser
+
+    :param domains: Optional list of domains to extract only from them
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def load(
+    domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+) -> CookieList:
+    """
+    Load Cookies from a browser
+
+    :param domains: Optional list of domains to load cookies from
+    :param profile: Optional profile directory, display name or index
+    :return: A list of dictionaries of cookies
+    """
+    ...
+
+def list_profiles() -> List[Dict[str, Any]]:
+    """
+    List profiles of installed browsers
+
+    :return: A list of dictionaries of profiles with their display name, account e-mail, channel and file paths
+    """
+    ...
+
+def any_browser(
+    db_path: str, domains: Optional[List[str]] = ..., key_path: Optional[str] = ...
+) -> List[Dict[str, str]]:
+    """
+    Extract Cookies from any browser.
+
+    :param db_path: Path to browser database file.
+    :param domains: Optional list of domains to extract cookies only from these domains.
+    :param key_path: Optional path to key file used to decrypt `db_path`.
+    :return: A list of dictionaries of cookies.
+    """
+    ...
+
+# Windows
+if platform == "win32":
+    def internet_explorer(
+        domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+    ) -> CookieList:
+        """
+        Extract Cookies from Internet Explorer
+
+        :param domains: Optional list of domains to extract only from them
+        :param profile: Optional profile directory, display name or index
+        :return: A list of dictionaries of cookies
+        """
+        ...
+
+    def octo_browser(
+        domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+    ) -> CookieList:
+        """
+        Extract Cookies from Octo browser
+
+        :param domains: Optional list of domains to extract only from them
+        :param profile: Optional profile directory, display name or index
+        :return: A list of dictionaries of cookies
+        """
+        ...
+
+# MacOS
+if platform == "darwin":
+    def safari(
+        domains: Optional[List[str]] = None, profile: Optional[Union[str, int]] = None
+    ) -> CookieList:
+        """
+        Extract Cookies from Safari browser
+
+        :param domains: Optional list of domains to extract only from them
+        :param profile: Optional profile directory, display name or index
+        :return: A list of dictionaries of cookies
+        """
+        ...
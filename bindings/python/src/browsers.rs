@@ -1,7 +1,43 @@
-use crate::to_dict;
-use pyo3::prelude::*;
+use crate::{errors::to_py_err, to_dict};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rookie::{enums::Cookie, ChromiumKeySource, ChromiumPlatform, CookieQuery, ProfileSelector};
 use std::path::PathBuf;
 
+/// Profile directory, display name or index
+#[derive(FromPyObject)]
+pub enum ProfileArg {
+  Index(usize),
+  Name(String),
+}
+
+impl From<ProfileArg> for ProfileSelector {
+  fn from(profile: ProfileArg) -> Self {
+    match profile {
+      ProfileArg::Index(index) => ProfileSelector::Index(index),
+      ProfileArg::Name(name) => ProfileSelector::Name(name),
+    }
+  }
+}
+
+/// Extracts cookies from a browser by its config name, or from all browsers
+fn query_cookies(
+  browser: Option<&str>,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> rookie::Result<Vec<Cookie>> {
+  let mut query = CookieQuery::new();
+  if let Some(browser) = browser {
+    query = query.browser(browser);
+  }
+  if let Some(domains) = domains {
+    query = query.domains(domains);
+  }
+  if let Some(profile) = profile {
+    query = query.profile(profile);
+  }
+  query.run()
+}
+
 /// Extract Cookies from any browser
 ///
 /// :param domains: Optional list of domains to extract only from them
@@ -13,7 +49,7 @@ pub fn any_browser(
   domains: Option<Vec<String>>,
   key_path: Option<&str>,
 ) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::any_browser(db_path, domains, key_path)?;
+  let cookies = rookie::any_browser(db_path, domains, key_path).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -22,10 +58,15 @@ pub fn any_browser(
 /// Extract Cookies from Firefox
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn firefox(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::firefox(domains)?;
+pub fn firefox(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("firefox"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -34,10 +75,15 @@ pub fn firefox(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObjec
 /// Extract Cookies from Zen
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn zen(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::zen(domains)?;
+pub fn zen(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("zen"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -46,10 +92,15 @@ pub fn zen(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>>
 /// Extract Cookies from LibreWolf browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn librewolf(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::librewolf(domains)?;
+pub fn librewolf(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("librewolf"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -58,10 +109,15 @@ pub fn librewolf(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObj
 /// Extract Cookies from Google Chrome browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn chrome(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::chrome(domains)?;
+pub fn chrome(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("chrome"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -70,10 +126,15 @@ pub fn chrome(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject
 /// Extract Cookies from Arc browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn arc(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::arc(domains)?;
+pub fn arc(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("arc"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -82,10 +143,15 @@ pub fn arc(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>>
 /// Extract Cookies from Brave browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn brave(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::brave(domains)?;
+pub fn brave(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("brave"), domains, profile).map_err(to_py_err)?;
 
   let cookies = to_dict(py, cookies)?;
 
@@ -95,10 +161,15 @@ pub fn brave(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>
 /// Extract Cookies from Microsoft Edge browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn edge(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::edge(domains)?;
+pub fn edge(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("edge"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -107,10 +178,15 @@ pub fn edge(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>>
 /// Extract Cookies from Opera browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn opera(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::opera(domains)?;
+pub fn opera(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("opera"), domains, profile).map_err(to_py_err)?;
 
   let cookies = to_dict(py, cookies)?;
 
@@ -120,10 +196,15 @@ pub fn opera(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>
 /// Extract Cookies from Opera GX browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn opera_gx(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::opera_gx(domains)?;
+pub fn opera_gx(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("opera_gx"), domains, profile).map_err(to_py_err)?;
 
   let cookies = to_dict(py, cookies)?;
 
@@ -133,10 +214,15 @@ pub fn opera_gx(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObje
 /// Extract Cookies from Chromium browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn chromium(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::chromium(domains)?;
+pub fn chromium(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("chromium"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -145,10 +231,15 @@ pub fn chromium(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObje
 /// Extract Cookies from Vivaldi browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn vivaldi(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::vivaldi(domains)?;
+pub fn vivaldi(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("vivaldi"), domains, profile).map_err(to_py_err)?;
 
   let cookies = to_dict(py, cookies)?;
 
@@ -167,7 +258,7 @@ pub fn firefox_based(
   db_path: String,
   domains: Option<Vec<String>>,
 ) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::firefox_based(PathBuf::from(db_path), domains)?;
+  let cookies = rookie::firefox_based(PathBuf::from(db_path), domains).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -176,10 +267,15 @@ pub fn firefox_based(
 /// Load Cookies from a browser
 ///
 /// :param domains: Optional list of domains to load cookies from
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-pub fn load(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::load(domains)?;
+pub fn load(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(None, domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -188,11 +284,16 @@ pub fn load(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>>
 /// Extract Cookies from Octo browser
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
 #[cfg(target_os = "windows")]
-pub fn octo_browser(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::octo_browser(domains)?;
+pub fn octo_browser(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("octo_browser"), domains, profile).map_err(to_py_err)?;
 
   let cookies = to_dict(py, cookies)?;
 
@@ -202,71 +303,144 @@ pub fn octo_browser(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<Py
 /// Extract Cookies from Internet Explorer
 ///
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
 #[cfg(target_os = "windows")]
-pub fn internet_explorer(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::internet_explorer(domains)?;
+pub fn internet_explorer(
+  py: Python,
+  domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
+) -> PyResult<Vec<PyObject>> {
+  let cookies = query_cookies(Some("ie"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
 }
 
-/// Extract Cookies from Chromium-based browsers
+/// Extract Cookies from Safari browser
 ///
-/// :param db_path: Path to the database file
 /// :param domains: Optional list of domains to extract only from them
+/// :param profile: Optional profile directory, display name or index
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-#[cfg(target_os = "windows")]
-pub fn chromium_based(
+#[cfg(target_os = "macos")]
+pub fn safari(
   py: Python,
-  key_path: String,
-  db_path: String,
   domains: Option<Vec<String>>,
+  profile: Option<ProfileArg>,
 ) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::chromium_based(PathBuf::from(key_path), PathBuf::from(db_path), domains)?;
+  let cookies = query_cookies(Some("safari"), domains, profile).map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
 }
 
-/// Extract Cookies from Safari browser
+/// Extract Cookies from Chromium-based browsers
 ///
+/// At most one of `key_path`, `browser`, `password` and `key` can be set,
+/// the keyring password of Chrome is used by default.
+///
+/// :param key_path: Path to the `Local State` file, or None to use another key source
+/// :param db_path: Path to the database file
 /// :param domains: Optional list of domains to extract only from them
+/// :param browser: Optional browser name whose keyring password is used (Linux, macOS)
+/// :param password: Optional `Safe Storage` password (Linux, macOS)
+/// :param key: Optional AES key
+/// :param platform: Optional OS the profile comes from (`linux`, `macos` or `windows`), to decrypt
+///   a copied profile with its password or key
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
-#[cfg(target_os = "macos")]
-pub fn safari(py: Python, domains: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
-  let cookies = rookie::safari(domains)?;
-  let cookies = to_dict(py, cookies)?;
-
-  Ok(cookies)
+#[cfg(target_os = "windows")]
+#[pyo3(signature = (key_path, db_path, domains = None, *, browser = None, password = None, key = None, platform = None))]
+#[allow(clippy::too_many_arguments)]
+pub fn chromium_based(
+  py: Python,
+  key_path: Option<String>,
+  db_path: String,
+  domains: Option<Vec<String>>,
+  browser: Option<String>,
+  password: Option<String>,
+  key: Option<Vec<u8>>,
+  platform: Option<String>,
+) -> PyResult<Vec<PyObject>> {
+  chromium_based_from(
+    py, db_path, domains, key_path, browser, password, key, platform,
+  )
 }
 
 /// Extract Cookies from Chromium-based browsers
 ///
+/// At most one of `key_path`, `browser`, `password` and `key` can be set,
+/// the keyring password of Chrome is used by default.
+///
 /// :param db_path: Path to the database file
 /// :param domains: Optional list of domains to extract only from them
+/// :param key_path: Optional path to the `Local State` file of a Windows profile
+/// :param browser: Optional browser name whose keyring password is used
+/// :param password: Optional `Safe Storage` password
+/// :param key: Optional AES key
+/// :param platform: Optional OS the profile comes from (`linux`, `macos` or `windows`), to decrypt
+///   a copied profile with its password or key
 /// :return: A list of dictionaries of cookies
 #[pyfunction]
 #[cfg(unix)]
+#[pyo3(signature = (db_path, domains = None, *, key_path = None, browser = None, password = None, key = None, platform = None))]
+#[allow(clippy::too_many_arguments)]
 pub fn chromium_based(
   py: Python,
   db_path: String,
   domains: Option<Vec<String>>,
+  key_path: Option<String>,
+  browser: Option<String>,
+  password: Option<String>,
+  key: Option<Vec<u8>>,
+  platform: Option<String>,
+) -> PyResult<Vec<PyObject>> {
+  chromium_based_from(
+    py, db_path, domains, key_path, browser, password, key, platform,
+  )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn chromium_based_from(
+  py: Python,
+  db_path: String,
+  domains: Option<Vec<String>>,
+  key_path: Option<String>,
+  browser: Option<String>,
+  password: Option<String>,
+  key: Option<Vec<u8>>,
+  platform: Option<String>,
 ) -> PyResult<Vec<PyObject>> {
-  use rookie::config::Browser;
-
-  let db_path = db_path.as_str();
-  let config = Browser {
-    channels: None,
-    paths: vec![db_path.to_string()],
-    unix_crypt_name: Some("chrome".to_string()),
-    osx_key_service: None,
-    osx_key_user: None,
-  };
-  let cookies = rookie::chromium_based(&config, PathBuf::from(db_path), domains)?;
+  let mut sources: Vec<ChromiumKeySource> = [
+    key_path.map(|key_path| ChromiumKeySource::LocalState(key_path.into())),
+    browser.map(ChromiumKeySource::Browser),
+    password.map(ChromiumKeySource::Password),
+    key.map(ChromiumKeySource::Key),
+  ]
+  .into_iter()
+  .flatten()
+  .collect();
+  if sources.len() > 1 {
+    return Err(PyValueError::new_err(
+      "only one of key_path, browser, password and key can be set",
+    ));
+  }
+  let key_source = sources
+    .pop()
+    .unwrap_or(ChromiumKeySource::Browser("chrome".to_string()));
+  let db_path = PathBuf::from(db_path);
+  let cookies = match platform {
+    Some(platform) => {
+      let platform = platform
+        .parse::<ChromiumPlatform>()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+      rookie::chromium_based_offline(platform, key_source, db_path, domains)
+    }
+    None => rookie::chromium_based(key_source, db_path, domains),
+  }
+  .map_err(to_py_err)?;
   let cookies = to_dict(py, cookies)?;
 
   Ok(cookies)
@@ -0,0 +1,117 @@
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use rookie::RookieError as Error;
+
+create_exception!(
+  rookiepy,
+  RookieError,
+  PyException,
+  "Base class of rookie errors"
+);
+create_exception!(
+  rookiepy,
+  BrowserNotInstalledError,
+  RookieError,
+  "Browser is not installed or has no cookies file"
+);
+create_exception!(
+  rookiepy,
+  UnsupportedBrowserError,
+  RookieError,
+  "Browser is not supported on this platform"
+);
+create_exception!(
+  rookiepy,
+  KeyringLockedError,
+  RookieError,
+  "Keyring holding the decryption key is locked"
+);
+create_exception!(
+  rookiepy,
+  DatabaseLockedError,
+  RookieError,
+  "Cookies database is locked by another process"
+);
+create_exception!(
+  rookiepy,
+  DecryptionFailedError,
+  RookieError,
+  "Cookies can't be decrypted"
+);
+create_exception!(
+  rookiepy,
+  UnsupportedSchemaError,
+  RookieError,
+  "Cookies file layout is not supported"
+);
+create_exception!(
+  rookiepy,
+  PermissionDeniedError,
+  RookieError,
+  "Permission denied for cookies file"
+);
+create_exception!(
+  rookiepy,
+  OperationTimeoutError,
+  RookieError,
+  "Operation didn't finish in time"
+);
+create_exception!(
+  rookiepy,
+  CancelledError,
+  RookieError,
+  "Operation was cancelled"
+);
+
+pub fn add_exceptions(py: Python, m: &PyModule) -> PyResult<()> {
+  m.add("RookieError", py.get_type::<RookieError>())?;
+  m.add(
+    "BrowserNotInstalledError",
+    py.get_type::<BrowserNotInstalledError>(),
+  )?;
+  m.add(
+    "UnsupportedBrowserError",
+    py.get_type::<UnsupportedBrowserError>(),
+  )?;
+  m.add("KeyringLockedError", py.get_type::<KeyringLockedError>())?;
+  m.add("DatabaseLockedError", py.get_type::<DatabaseLockedError>())?;
+  m.add(
+    "DecryptionFailedError",
+    py.get_type::<DecryptionFailedError>(),
+  )?;
+  m.add(
+    "UnsupportedSchemaError",
+    py.get_type::<UnsupportedSchemaError>(),
+  )?;
+  m.add(
+    "PermissionDeniedError",
+    py.get_type::<PermissionDeniedError>(),
+  )?;
+  m.add(
+    "OperationTimeoutError",
+    py.get_type::<OperationTimeoutError>(),
+  )?;
+  m.add("CancelledError", py.get_type::<CancelledError>())?;
+  Ok(())
+}
+
+/// Converts rookie error to the matching python exception
+pub fn to_py_err(error: Error) -> PyErr {
+  let mut message = error.to_string();
+  let mut source = std::error::Error::source(&error);
+  while let Some(cause) = source {
+    message += &format!("\nCaused by: {}", cause);
+    source = cause.source();
+  }
+  match error {
+    Error::BrowserNotInstalled(_) => BrowserNotInstalledError::new_err(message),
+    Error::UnsupportedBrowser(_) => UnsupportedBrowserError::new_err(message),
+    Error::KeyringLocked(_) => KeyringLockedError::new_err(message),
+    Error::DatabaseLocked { .. } => DatabaseLockedError::new_err(message),
+    Error::DecryptionFailed(_) => DecryptionFailedError::new_err(message),
+    Error::UnsupportedSchema(_) => UnsupportedSchemaError::new_err(message),
+    Error::PermissionDenied { .. } => PermissionDeniedError::new_err(message),
+    Error::Timeout { .. } => OperationTimeoutError::new_err(message),
+    Error::Cancelled(_) => CancelledError::new_err(message),
+    Error::Other(_) => RookieError::new_err(message),
+  }
+}
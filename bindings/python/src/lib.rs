@@ -1,6 +1,10 @@
-use pyo3::{prelude::*, types::PyDict};
+use pyo3::{
+  prelude::*,
+  types::{PyBytes, PyDict},
+};
 use rookie::enums::Cookie;
 mod browsers;
+mod errors;
 use browsers::*;
 
 #[pyfunction]
@@ -8,9 +12,39 @@ fn version() -> PyResult<String> {
   Ok(rookie::version())
 }
 
+/// Forget the keyring passwords cached since the first extraction
+#[pyfunction]
+fn clear_key_cache() {
+  rookie::keys::clear_key_cache()
+}
+
+/// List profiles of installed browsers
+///
+/// :return: A list of dictionaries of profiles
+#[pyfunction]
+fn list_profiles(py: Python) -> PyResult<Vec<PyObject>> {
+  let mut profile_objects: Vec<PyObject> = vec![];
+  for profile in rookie::list_profiles() {
+    let dict = PyDict::new(py);
+    dict.set_item("display_name", profile.display_name())?;
+    dict.set_item("browser", profile.browser)?;
+    dict.set_item("channel", profile.channel)?;
+    dict.set_item("directory", profile.directory)?;
+    dict.set_item("name", profile.name)?;
+    dict.set_item("email", profile.email)?;
+    dict.set_item("is_default", profile.is_default)?;
+    dict.set_item("db_path", profile.db_path)?;
+    dict.set_item("key_path", profile.key_path)?;
+
+    profile_objects.push(dict.to_object(py));
+  }
+  Ok(profile_objects)
+}
+
 #[pymodule]
-fn rookiepy(_py: Python, m: &PyModule) -> PyResult<()> {
+fn rookiepy(py: Python, m: &PyModule) -> PyResult<()> {
   pyo3_log::init();
+  errors::add_exceptions(py, m)?;
   m.add_function(wrap_pyfunction!(firefox, m)?)?;
   m.add_function(wrap_pyfunction!(zen, m)?)?;
 
@@ -39,7 +73,9 @@ fn rookiepy(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(safari, m)?)?;
   }
 
+  m.add_function(wrap_pyfunction!(list_profiles, m)?)?;
   m.add_function(wrap_pyfunction!(version, m)?)?;
+  m.add_function(wrap_pyfunction!(clear_key_cache, m)?)?;
   Ok(())
 }
 
@@ -47,14 +83,25 @@ fn to_dict(py: Python, cookies: Vec<Cookie>) -> PyResult<Vec<PyObject>> {
   let mut cookie_objects: Vec<PyObject> = vec![];
   for cookie in cookies {
     let dict = PyDict::new(py);
+    dict.set_item("value_bytes", PyBytes::new(py, cookie.raw_value()))?;
     dict.set_item("domain", cookie.domain)?;
     dict.set_item("path", cookie.path)?;
     dict.set_item("secure", cookie.secure)?;
     dict.set_item("http_only", cookie.http_only)?;
-    dict.set_item("same_site", cookie.same_site)?;
+    dict.set_item("same_site", cookie.same_site.as_str())?;
+    dict.set_item("same_site_raw", cookie.same_site_raw)?;
     dict.set_item("expires", cookie.expires)?;
     dict.set_item("name", cookie.name)?;
     dict.set_item("value", cookie.value)?;
+    dict.set_item("profile", cookie.profile)?;
+    dict.set_item("creation", cookie.creation)?;
+    dict.set_item("last_accessed", cookie.last_accessed)?;
+    dict.set_item("last_update", cookie.last_update)?;
+    dict.set_item("priority", cookie.priority)?;
+    dict.set_item("source_scheme", cookie.source_scheme)?;
+    dict.set_item("source_port", cookie.source_port)?;
+    dict.set_item("scheme_map", cookie.scheme_map)?;
+    dict.set_item("is_persistent", cookie.is_persistent)?;
 
     cookie_objects.push(dict.to_object(py));
   }
@@ -24,6 +24,10 @@ pub struct Args {
   #[arg(short, long, value_parser = browser_keys())]
   pub browser: Option<String>,
 
+  /// Profile directory, display name or index to get cookies from (ignored with --path)
+  #[arg(long)]
+  pub profile: Option<String>,
+
   /// Get cookies from all possible browsers
   #[arg(short, long, default_missing_value = "true")]
   pub load: bool,
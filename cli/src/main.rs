@@ -1,5 +1,5 @@
 use clap::Parser;
-use rookie::{any_browser, common::enums::Cookie};
+use rookie::{any_browser, common::enums::Cookie, CookieQuery, ProfileSelector};
 mod browsers_map;
 use browsers_map::BROWSERS_MAP;
 mod args;
@@ -38,7 +38,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
   #[allow(unused_assignments)]
   let mut cookies = vec![];
   let args_c = args.clone();
-  if args.load {
+  // Paths given explicitly have no profile to select
+  let from_path = !args.load && args.browser.is_none() && args.path.is_some();
+  if let (Some(profile), false) = (&args.profile, from_path) {
+    let mut query = CookieQuery::new().profile(ProfileSelector::parse(profile));
+    if let (false, Some(browser)) = (args.load, &args.browser) {
+      query = query.browser(browser.replace(' ', "_"));
+    }
+    if let Some(domains) = args.domains {
+      query = query.domains(domains);
+    }
+    cookies = query.run()?;
+  } else if args.load {
     cookies = rookie::load(args.domains)?;
   } else if let Some(browser) = args.browser {
     let browser_fn = BROWSERS_MAP.get(&browser).unwrap();
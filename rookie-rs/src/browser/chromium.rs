@@ -1,40 +1,314 @@
-use crate::common::{date, enums::*, sqlite};
-use eyre::{bail, Result};
-use std::path::PathBuf;
-
-#[allow(unused)]
-use crate::config::Browser;
+use crate::common::{cancel::Deadline, date, domain::DomainFilter, enums::*, sqlite};
+#[cfg(unix)]
+use crate::keys::KeyRequest;
+use crate::keys::{self, DpapiMasterKeys, KeyProviders};
+use crate::{os_crypt, RookieError};
+use base64::{engine::general_purpose, Engine as _};
+use eyre::{Context, Result};
+use rusqlite::{params_from_iter, types::ValueRef};
+use sha2::{Digest, Sha256};
+use std::{
+  collections::HashMap,
+  fmt,
+  path::{Path, PathBuf},
+  str::FromStr,
+};
 
 #[cfg(target_os = "windows")]
 use crate::windows;
 
-#[cfg(not(target_os = "linux"))]
-#[allow(unused)]
-use eyre::ContextCompat;
+/// Cookies DB version from which decrypted values start with the SHA-256 of the cookie host
+const DOMAIN_HASH_VERSION: i64 = 24;
+
+/// Operating system a chromium profile comes from, which decides how its values are encrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChromiumPlatform {
+  /// AES-128-CBC, key derived from the `Safe Storage` password with 1 PBKDF2 iteration
+  Linux,
+  /// AES-128-CBC, key derived from the `Safe Storage` password with 1003 PBKDF2 iterations
+  MacOs,
+  /// AES-256-GCM, key kept in `Local State` encrypted with DPAPI
+  Windows,
+}
 
-#[cfg(target_os = "macos")]
-use crate::macos;
+impl ChromiumPlatform {
+  /// Returns the platform rookie runs on
+  pub fn current() -> Self {
+    if cfg!(windows) {
+      ChromiumPlatform::Windows
+    } else if cfg!(target_os = "macos") {
+      ChromiumPlatform::MacOs
+    } else {
+      ChromiumPlatform::Linux
+    }
+  }
 
-#[cfg(target_os = "windows")]
-use aes_gcm::{
-  aead::{generic_array::GenericArray, Aead, KeyInit},
-  Aes256Gcm, Key,
-};
-#[cfg(target_os = "windows")]
-use base64::{engine::general_purpose, Engine as _};
-#[cfg(target_os = "windows")]
-use eyre::Context;
+  fn key_length(&self) -> usize {
+    match self {
+      ChromiumPlatform::Windows => 32,
+      ChromiumPlatform::Linux | ChromiumPlatform::MacOs => 16,
+    }
+  }
+
+  fn pbkdf2_iterations(&self) -> u32 {
+    match self {
+      ChromiumPlatform::MacOs => 1003,
+      ChromiumPlatform::Linux | ChromiumPlatform::Windows => 1,
+    }
+  }
+}
+
+impl FromStr for ChromiumPlatform {
+  type Err = eyre::Report;
+
+  /// Parses `linux`, `macos` or `windows`
+  fn from_str(name: &str) -> Result<Self> {
+    match name.to_lowercase().as_str() {
+      "linux" => Ok(ChromiumPlatform::Linux),
+      "macos" | "osx" => Ok(ChromiumPlatform::MacOs),
+      "windows" => Ok(ChromiumPlatform::Windows),
+      _ => eyre::bail!(
+        "Unknown platform {}, expected linux, macos or windows",
+        name
+      ),
+    }
+  }
+}
+
+impl fmt::Display for ChromiumPlatform {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      ChromiumPlatform::Linux => "linux",
+      ChromiumPlatform::MacOs => "macos",
+      ChromiumPlatform::Windows => "windows",
+    };
+    f.write_str(name)
+  }
+}
+
+/// Where the key decrypting cookie values comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChromiumKeySource {
+  /// `Local State` file holding the key encrypted with DPAPI (Windows)
+  LocalState(PathBuf),
+  /// Browser config name (`chrome`, `brave`, ...) whose password is read from the keyring on
+  /// Linux and from the keychain on macOS
+  ///
+  /// On Windows the `Local State` file is searched next to the cookies database.
+  Browser(String),
+  /// `Safe Storage` password of the browser (Linux, macOS)
+  Password(String),
+  /// AES key, 16 bytes for Linux and macOS profiles, 32 bytes for Windows profiles (the master key
+  /// once unwrapped from `Local State`)
+  Key(Vec<u8>),
+}
 
 /// Returns cookies from chromium based browser
-#[cfg(target_os = "windows")]
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{chromium_based, ChromiumKeySource};
+///
+/// let db_path = "/home/user/.config/google-chrome/Default/Cookies";
+/// let source = ChromiumKeySource::Password("secret".to_string());
+/// let cookies = chromium_based(source, db_path.into(), None).unwrap();
+/// ```
 pub fn chromium_based(
-  key: PathBuf,
+  key_source: ChromiumKeySource,
   db_path: PathBuf,
   domains: Option<Vec<String>>,
-) -> Result<Vec<Cookie>> {
-  let content = std::fs::read_to_string(key)?;
-  let key_dict: serde_json::Value =
-    serde_json::from_str(content.as_str()).context("Can't read json file")?;
+) -> crate::Result<Vec<Cookie>> {
+  let filter = domains.into();
+  let platform = ChromiumPlatform::current();
+  Ok(
+    chromium_based_partial(
+      platform,
+      &key_source,
+      &keys::registered(),
+      &Deadline::default(),
+      db_path,
+      &filter,
+      DecryptionMode::Strict,
+    )?
+    .0,
+  )
+}
+
+/// Returns cookies from a chromium profile copied from another machine, which may run another OS
+///
+/// The key must be given as a [`ChromiumKeySource::Password`] or a [`ChromiumKeySource::Key`],
+/// keyrings and DPAPI of the machine running rookie can't decrypt it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{chromium_based_offline, ChromiumKeySource, ChromiumPlatform};
+///
+/// // Profile of a mac
+/// let db_path = "/evidence/Users/alice/Library/Application Support/Google/Chrome/Default/Cookies";
+/// let source = ChromiumKeySource::Password("safe storage password".to_string());
+/// let cookies = chromium_based_offline(ChromiumPlatform::MacOs, source, db_path.into(), None);
+/// ```
+pub fn chromium_based_offline(
+  platform: ChromiumPlatform,
+  key_source: ChromiumKeySource,
+  db_path: PathBuf,
+  domains: Option<Vec<String>>,
+) -> crate::Result<Vec<Cookie>> {
+  if let ChromiumKeySource::Browser(_) | ChromiumKeySource::LocalState(_) = key_source {
+    return Err(
+      eyre::eyre!("Offline profiles need their password or key, not the keyring of this machine")
+        .into(),
+    );
+  }
+  let filter = domains.into();
+  Ok(
+    chromium_based_partial(
+      platform,
+      &key_source,
+      &KeyProviders::new(),
+      &Deadline::default(),
+      db_path,
+      &filter,
+      DecryptionMode::Strict,
+    )?
+    .0,
+  )
+}
+
+/// Returns the key of a `Local State` file copied from Windows, unwrapped with the DPAPI master
+/// keys of its user, to give to [`chromium_based_offline`] as a [`ChromiumKeySource::Key`]
+///
+/// Values encrypted with the app-bound key (`v20`) of recent Chrome versions also need the keys of
+/// the SYSTEM account, they aren't decrypted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{
+///   chromium_based_offline, chromium_local_state_key,
+///   keys::{DpapiMasterKeys, DpapiSecret},
+///   ChromiumKeySource, ChromiumPlatform,
+/// };
+///
+/// let app_data = "/evidence/Users/alice/AppData";
+/// let master_keys = DpapiMasterKeys::new(
+///   format!("{app_data}/Roaming/Microsoft/Protect/S-1-5-21-1-2-3-1001"),
+///   DpapiSecret::Password("hunter2".to_string()),
+/// );
+/// let user_data = format!("{app_data}/Local/Google/Chrome/User Data");
+/// let key = chromium_local_state_key(format!("{user_data}/Local State").into(), &master_keys);
+/// let db_path = format!("{user_data}/Default/Network/Cookies");
+/// let source = ChromiumKeySource::Key(key.unwrap());
+/// let cookies = chromium_based_offline(ChromiumPlatform::Windows, source, db_path.into(), None);
+/// ```
+pub fn chromium_local_state_key(
+  local_state: PathBuf,
+  master_keys: &DpapiMasterKeys,
+) -> crate::Result<Vec<u8>> {
+  let key_dict = read_local_state(&local_state)?;
+  let encrypted_key = key_dict["os_crypt"]["encrypted_key"]
+    .as_str()
+    .unwrap_or_default();
+  master_keys.unprotect(&decode_dpapi_key(encrypted_key)?)
+}
+
+/// Same as [`chromium_based`], also returns the number of values which couldn't be decrypted
+pub(crate) fn chromium_based_partial(
+  platform: ChromiumPlatform,
+  key_source: &ChromiumKeySource,
+  providers: &KeyProviders,
+  deadline: &Deadline,
+  db_path: PathBuf,
+  filter: &DomainFilter,
+  mode: DecryptionMode,
+) -> crate::Result<(Vec<Cookie>, usize)> {
+  let (keys, keyring_error) = read_keys(platform, key_source, providers, &db_path)?;
+  let (cookies, failed) = match query_cookies(platform, &keys, db_path, filter, mode, deadline) {
+    Ok(result) => result,
+    Err(e) => {
+      // Report the locked keyring rather than its consequence
+      return Err(match (RookieError::from(e), keyring_error) {
+        (RookieError::DecryptionFailed(_), Some(keyring_error)) => keyring_error,
+        (e, _) => e,
+      });
+    }
+  };
+  if let Some(keyring_error) = keyring_error.filter(|_| failed > 0) {
+    log::warn!("{} values couldn't be decrypted: {}", failed, keyring_error);
+  }
+  Ok((cookies, failed))
+}
+
+/// Returns keys of a key source along with the keyring error which prevented reading the
+/// password, if any
+#[allow(unused_variables)]
+pub(crate) fn read_keys(
+  platform: ChromiumPlatform,
+  key_source: &ChromiumKeySource,
+  providers: &KeyProviders,
+  db_path: &Path,
+) -> Result<(Vec<Vec<u8>>, Option<RookieError>)> {
+  match key_source {
+    ChromiumKeySource::Key(key) if key.len() != platform.key_length() => Err(
+      RookieError::DecryptionFailed(format!(
+        "{} keys must be {} bytes long, got {}",
+        platform,
+        platform.key_length(),
+        key.len()
+      ))
+      .into(),
+    ),
+    ChromiumKeySource::Key(key) => {
+      let mut keys = vec![key.clone()];
+      if platform != ChromiumPlatform::Windows {
+        keys.extend(password_keys(platform, &[]));
+      }
+      Ok((keys, None))
+    }
+    ChromiumKeySource::Password(_) if platform == ChromiumPlatform::Windows => {
+      eyre::bail!("Passwords aren't used on Windows, use the Local State file or a key")
+    }
+    ChromiumKeySource::Password(password) => Ok((
+      password_keys(platform, std::slice::from_ref(password)),
+      None,
+    )),
+    _ if platform != ChromiumPlatform::current() => {
+      eyre::bail!(
+        "Keys of {} profiles can't be read on {}, give their password or key instead",
+        platform,
+        ChromiumPlatform::current()
+      )
+    }
+    #[cfg(target_os = "windows")]
+    ChromiumKeySource::LocalState(path) => Ok((read_local_state_keys(path.clone())?, None)),
+    #[cfg(target_os = "windows")]
+    ChromiumKeySource::Browser(_) => {
+      let local_state = db_path
+        .ancestors()
+        .map(|dir| dir.join("Local State"))
+        .find(|path| path.exists())
+        .ok_or_else(|| eyre::eyre!("Local State not found next to {}", db_path.display()))?;
+      Ok((read_local_state_keys(local_state)?, None))
+    }
+    #[cfg(unix)]
+    ChromiumKeySource::LocalState(_) => {
+      eyre::bail!("Local State holds no key on this platform, use the browser or its password")
+    }
+    #[cfg(unix)]
+    ChromiumKeySource::Browser(name) => {
+      let config = crate::config::find_browser_config(name)
+        .ok_or_else(|| RookieError::UnsupportedBrowser(name.clone()))?;
+      get_keys(&KeyRequest::new(name, config), providers)
+    }
+  }
+}
+
+/// Returns keys from the `Local State` file
+#[cfg(target_os = "windows")]
+fn read_local_state_keys(key: PathBuf) -> Result<Vec<Vec<u8>>> {
+  let key_dict = read_local_state(&key)?;
 
   let legacy_key = key_dict["os_crypt"]["encrypted_key"]
     .as_str()
@@ -47,38 +321,38 @@ pub fn chromium_based(
 
   #[cfg(feature = "appbound")]
   {
-    let keys = if !appbound_key.is_empty() {
+    if !appbound_key.is_empty() {
       if !privilege::user::privileged() {
-        bail!("Chrome cookies from version v130 can be decrypted only when running as admin due to appbound encryption!")
+        return Err(
+          RookieError::PermissionDenied {
+            path: key,
+            hint: Some("Chrome cookies from version v130 can be decrypted only when running as admin due to appbound encryption!".to_string()),
+            source: std::io::ErrorKind::PermissionDenied.into(),
+          }
+          .into(),
+        );
       }
-      crate::windows::appbound::get_keys(appbound_key)?
-    } else {
-      get_keys(legacy_key)?
-    };
-    query_cookies(keys, db_path, domains)
+      return crate::windows::appbound::get_keys(appbound_key);
+    }
   }
 
-  #[cfg(not(feature = "appbound"))]
-  {
-    let keys = get_keys(legacy_key)?;
-    query_cookies(keys, db_path, domains)
-  }
+  get_keys(legacy_key)
 }
 
-/// Returns cookies from chromium based browser
-#[cfg(unix)]
-pub fn chromium_based(
-  config: &Browser,
-  db_path: PathBuf,
-  domains: Option<Vec<String>>,
-) -> Result<Vec<Cookie>> {
-  // Simple AES
+fn read_local_state(path: &Path) -> Result<serde_json::Value> {
+  let content = std::fs::read_to_string(path)?;
+  serde_json::from_str(content.as_str()).context("Can't read json file")
+}
 
-  let keys = get_keys(config)?;
-  query_cookies(keys, db_path, domains)
+/// Decodes the `os_crypt.encrypted_key` of `Local State`, a DPAPI blob prefixed with `DPAPI`
+fn decode_dpapi_key(key64: &str) -> Result<Vec<u8>> {
+  let key = general_purpose::STANDARD.decode(key64)?;
+  match key.strip_prefix(b"DPAPI") {
+    Some(blob) => Ok(blob.to_vec()),
+    None => eyre::bail!("Local State holds no DPAPI key"),
+  }
 }
 
-#[cfg(unix)]
 fn create_pbkdf2_key(password: &str, salt: &[u8; 9], iterations: u32) -> Vec<u8> {
   use pbkdf2::pbkdf2_hmac;
   use sha1::Sha1;
@@ -87,180 +361,109 @@ fn create_pbkdf2_key(password: &str, salt: &[u8; 9], iterations: u32) -> Vec<u8>
   output.to_vec()
 }
 
+/// Derives keys from `Safe Storage` passwords, followed by the default keys
+fn password_keys(platform: ChromiumPlatform, passwords: &[String]) -> Vec<Vec<u8>> {
+  let salt = b"saltysalt";
+  passwords
+    .iter()
+    .map(String::as_str)
+    .chain(["peanuts", ""])
+    .map(|password| create_pbkdf2_key(password, salt, platform.pbkdf2_iterations()))
+    .collect()
+}
+
 #[cfg(target_os = "windows")]
 fn get_keys(key64: &str) -> Result<Vec<Vec<u8>>> {
-  let mut keydpapi: Vec<u8> = general_purpose::STANDARD.decode(key64)?;
-  let keydpapi = &mut keydpapi[5..];
-  let v10_key = crate::windows::dpapi::decrypt(keydpapi)?;
+  let mut keydpapi = decode_dpapi_key(key64)?;
+  let v10_key = crate::windows::dpapi::decrypt(&mut keydpapi)?;
   let keys: Vec<Vec<u8>> = vec![v10_key];
   Ok(keys)
 }
 
-#[cfg(target_os = "linux")]
-fn get_keys(config: &Browser) -> Result<Vec<Vec<u8>>> {
+/// Returns keys along with the keyring error which prevented reading the password, if any
+#[cfg(unix)]
+pub(crate) fn get_keys(
+  request: &KeyRequest,
+  providers: &KeyProviders,
+) -> Result<(Vec<Vec<u8>>, Option<RookieError>)> {
   // AES CBC key
-
-  let salt = b"saltysalt";
-
-  let iterations = 1;
-
-  let mut keys: Vec<Vec<u8>> = vec![];
-  if let Ok(passwords) =
-    crate::linux::get_passwords(&config.unix_crypt_name.clone().unwrap_or("".to_owned()))
-  {
-    for password in passwords {
-      let key = create_pbkdf2_key(password.as_str(), salt, iterations);
-      keys.push(key);
-    }
-  }
-  // default keys
-  let key = create_pbkdf2_key("peanuts", salt, iterations);
-  keys.push(key);
-  let key = create_pbkdf2_key("", salt, iterations);
-  keys.push(key);
-
-  Ok(keys)
+  let platform = ChromiumPlatform::current();
+  Ok(providers.keys(request, platform, |passwords| {
+    password_keys(platform, passwords)
+  })?)
 }
 
-#[cfg(target_os = "macos")]
-fn get_keys(config: &Browser) -> Result<Vec<Vec<u8>>> {
-  let salt = b"saltysalt";
-
-  let iterations = 1003;
-
-  let mut keys: Vec<Vec<u8>> = vec![];
-
-  let key_service = config
-    .osx_key_service
-    .clone()
-    .context("missing osx_key_service")?;
-  let key_user = config
-    .osx_key_user
-    .clone()
-    .context("missing osx_key_user")?;
-  let password =
-    macos::get_osx_keychain_password(&key_service, &key_user).unwrap_or("peanuts".to_string());
-
-  let key = create_pbkdf2_key(password.as_str(), salt, iterations);
-  keys.push(key);
-
-  let key = create_pbkdf2_key("peanuts", salt, iterations);
-  keys.push(key);
-  let key = create_pbkdf2_key("", salt, iterations);
-  keys.push(key);
-
-  Ok(keys)
+/// Returns true if the value is encrypted with a known key type
+fn is_encrypted(encrypted_value: &[u8]) -> bool {
+  os_crypt::Version::of(encrypted_value).is_some()
 }
 
-/// Decrypt cookie value using aes GCM
-#[cfg(windows)]
-fn decrypt_encrypted_value(
-  value: String,
-  encrypted_value: &[u8],
-  keys: Vec<Vec<u8>>,
-) -> Result<String> {
-  let key_type = &encrypted_value[..3];
-  if !value.is_empty() || !(key_type == b"v11" || key_type == b"v10" || key_type == b"v20") {
-    // unknown key_type or value isn't encrypted
-    log::warn!("Unknown key type: {:?}", key_type);
-    return Ok(value);
-  }
-  log::debug!("key type: {:?}", key_type);
-
-  let encrypted_value = &encrypted_value[3..];
-  let nonce = &encrypted_value[..12]; // iv
-  let ciphertext = &encrypted_value[12..];
-
-  // Create a new AES block cipher.
-  for key in keys {
-    let key = Key::<Aes256Gcm>::from_slice(key.as_slice());
-    let cipher = Aes256Gcm::new(key);
-    let nonce = GenericArray::from_slice(nonce); // 96-bits; unique per message
-
-    match cipher.decrypt(nonce, ciphertext.as_ref()) {
-      Ok(plaintext) => {
-        // Successfully decrypted, try to convert to string
-        let plaintext = if key_type == b"v20" {
-          String::from_utf8(plaintext[32..].to_vec()).context("Can't decode encrypted value")
-        } else {
-          String::from_utf8(plaintext).context("Can't decode encrypted value")
-        };
-
-        match plaintext {
-          Ok(text) => return Ok(text),
-          Err(e) => log::warn!("Failed to decode plaintext: {}", e),
-        }
-      }
-      Err(e) => {
-        // We'll get error anyway if decryption failed
-        log::debug!("Failed to decrypt with a key: {}", e);
-        continue;
-      }
+/// Strips the SHA-256 of the cookie host which prefixes plaintexts from cookies DB version 24,
+/// plaintexts without it are kept as is
+fn strip_domain_hash(host_key: &str, plaintext: Vec<u8>) -> (Vec<u8>, DecryptionStatus) {
+  let hash = Sha256::digest(host_key.as_bytes());
+  match plaintext.get(..hash.len()) {
+    Some(prefix) if prefix == hash.as_slice() => {
+      (plaintext[hash.len()..].to_vec(), DecryptionStatus::Ok)
+    }
+    _ => {
+      log::warn!(
+        "Value of a cookie of {} isn't prefixed by its domain hash",
+        host_key
+      );
+      (plaintext, DecryptionStatus::DomainHashMismatch)
     }
   }
-  bail!("decrypt_encrypted_value failed")
 }
 
-/// Decrypt cookie value using aes cbc
-#[cfg(unix)]
+/// Returns the cookies DB version from the `meta` table
+fn meta_version(connection: &rusqlite::Connection) -> Option<i64> {
+  connection
+    .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+      Ok(match row.get_ref(0)? {
+        ValueRef::Integer(version) => Some(version),
+        ValueRef::Text(version) => std::str::from_utf8(version)
+          .ok()
+          .and_then(|version| version.parse().ok()),
+        _ => None,
+      })
+    })
+    .ok()
+    .flatten()
+}
+
+/// Decrypts a cookie value encrypted the way the platform does
+///
+/// CBC isn't authenticated, a key is known to be right when the plaintext starts with the domain
+/// hash, or else when it's valid UTF-8 in databases without domain hashes.
 fn decrypt_encrypted_value(
-  value: String,
+  platform: ChromiumPlatform,
+  value: Vec<u8>,
   encrypted_value: &[u8],
-  keys: Vec<Vec<u8>>,
-) -> Result<String> {
-  // cbc
-  if !value.is_empty() {
+  keys: &[Vec<u8>],
+  domain_hash: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+  if !value.is_empty() || !is_encrypted(encrypted_value) {
     // unknown key_type or value isn't encrypted
     return Ok(value);
   }
-  if encrypted_value.is_empty() {
-    return Ok("".into());
-  }
-  let key_type = &encrypted_value[..3];
-
-  if !(key_type == b"v11" || key_type == b"v10" || key_type == b"v20") {
-    return Ok(value);
-  }
-  log::debug!("key type: {:?}", key_type);
-
-  use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
-
-  type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
-
-  // Create an AES-128 cipher with the provided key.
-
-  let encrypted_value = &mut encrypted_value.to_owned()[3..];
-  let iv: [u8; 16] = [b' '; 16];
-
-  for key in &keys {
-    let mut key_array: [u8; 16] = [0; 16];
-    key_array.copy_from_slice(&key[..16]);
-    let cipher = Aes128CbcDec::new(&key_array.into(), &iv.into());
-    let mut cloned_encrypted_value: Vec<u8> = encrypted_value.to_vec();
-
-    if let Ok(plaintext) = cipher.decrypt_padded_mut::<Pkcs7>(&mut cloned_encrypted_value) {
-      let decoded = String::from_utf8(plaintext.to_vec());
-      match decoded {
-        Ok(decoded) => {
-          return Ok(decoded);
-        }
-        Err(_) => {
-          log::debug!("Error in decode decrypt value with utf8. trying from index 32");
-
-          let decoded = String::from_utf8(plaintext[32..].to_vec()).unwrap_or_else(|_| {
-            log::warn!("Error decoding from index 32 with UTF-8");
-            "".into()
-          });
-          return Ok(decoded);
-        }
-      }
+  log::debug!("key type: {:?}", &encrypted_value[..3]);
+  let plaintext = match platform {
+    ChromiumPlatform::Windows => os_crypt::decrypt_gcm(&encrypted_value[3..], keys),
+    ChromiumPlatform::Linux | ChromiumPlatform::MacOs => {
+      os_crypt::decrypt_cbc(&encrypted_value[3..], keys, |plaintext| match domain_hash {
+        Some(hash) => plaintext.starts_with(hash),
+        None => std::str::from_utf8(plaintext).is_ok(),
+      })
     }
-  }
-  bail!("decrypt_encrypted_value failed")
+  };
+  plaintext.ok_or_else(|| {
+    RookieError::DecryptionFailed("no key could decrypt the cookie value".to_string()).into()
+  })
 }
 
 #[cfg(target_os = "windows")]
-fn unlock_file(mut path: PathBuf) -> Result<PathBuf> {
+fn unlock_file(mut path: PathBuf, deadline: &Deadline) -> Result<PathBuf> {
   let mut shadow_copy_success = false;
   // Shadow copy cookies file so we can read session cookies
   // Admin rights required
@@ -279,67 +482,114 @@ fn unlock_file(mut path: PathBuf) -> Result<PathBuf> {
   // Elegantly restart the process which lock the cookies file (And unlock it) using restart manager API
   if !shadow_copy_success {
     log::warn!("Unlocking Chrome database... This may take a while (sometimes up to a minute)");
-    unsafe {
-      crate::windows::restart_manager::release_file_lock(path.to_str().unwrap());
-    }
+    let file_path = path.to_str().unwrap().to_string();
+    windows::restart_manager::release_file_lock_within(&file_path, deadline)?;
   }
   Ok(path)
 }
 
-#[allow(unused_mut)]
+/// Returns cookies along with the number of values which couldn't be decrypted
+#[allow(unused_mut, unused_variables)]
 fn query_cookies(
-  keys: Vec<Vec<u8>>,
+  platform: ChromiumPlatform,
+  keys: &[Vec<u8>],
   mut db_path: PathBuf,
-  domains: Option<Vec<String>>,
-) -> Result<Vec<Cookie>> {
+  filter: &DomainFilter,
+  mode: DecryptionMode,
+  deadline: &Deadline,
+) -> Result<(Vec<Cookie>, usize)> {
   // In windows unlock file locking
   #[cfg(target_os = "windows")]
   {
-    db_path = unlock_file(db_path)?;
+    db_path = unlock_file(db_path, deadline)?;
   }
 
   log::info!(
     "Creating SQLite connection to {}",
     db_path.to_str().unwrap_or("")
   );
-  let connection = sqlite::connect(db_path)?;
-  let mut query =
-        "SELECT host_key, path, is_secure, expires_utc, name, value, CAST(encrypted_value AS BLOB), is_httponly, samesite FROM cookies ".to_string();
-
-  if let Some(domains) = domains {
-    let domain_queries: Vec<String> = domains
-      .iter()
-      .map(|domain| format!("host_key LIKE '%{}%'", domain))
-      .collect();
-
-    if !domain_queries.is_empty() {
-      let joined_queries = domain_queries.join(" OR ");
-      query += &format!("WHERE ({})", joined_queries);
-    }
+  let connection = sqlite::connect(db_path.clone())?;
+  let version = meta_version(&connection);
+  log::debug!("Cookies DB version: {:?}", version);
+  let hashed_domains = version.is_some_and(|version| version >= DOMAIN_HASH_VERSION);
+  let optional_columns = [
+    "creation_utc",
+    "last_access_utc",
+    "last_update_utc",
+    "priority",
+    "source_scheme",
+    "source_port",
+    "is_persistent",
+  ]
+  .iter()
+  .map(|column| sqlite::optional_column(&connection, "cookies", column))
+  .collect::<Result<Vec<String>>>()?;
+  let mut query = format!(
+    "SELECT host_key, path, is_secure, expires_utc, name, value, CAST(encrypted_value AS BLOB), is_httponly, samesite, {} FROM cookies ",
+    optional_columns.join(", ")
+  );
+
+  let patterns = filter.like_patterns().unwrap_or_default();
+  if !patterns.is_empty() {
+    query += &sqlite::like_clause("host_key", &patterns);
   }
   query += ";";
 
   let mut cookies: Vec<Cookie> = vec![];
-  let mut stmt = connection.prepare(query.as_str())?;
-  let mut rows = stmt.query([])?;
+  let mut undecoded = 0;
+  let mut stmt = connection
+    .prepare(query.as_str())
+    .map_err(|e| sqlite::map_error(&db_path, e))?;
+  let mut rows = stmt.query(params_from_iter(&patterns))?;
 
   while let Some(row) = rows.next()? {
     let host_key: String = row.get(0)?;
+    if !filter.matches(&host_key) {
+      continue;
+    }
     let path: String = row.get(1)?;
     let is_secure: bool = row.get(2)?;
     let expires: u64 = row.get(3)?;
     let expires = date::chromium_timestamp(expires);
     let name: String = row.get(4)?;
 
-    let value: String = row.get(5)?;
+    // Unencrypted values are text, yet they may hold any bytes
+    let value = row.get_ref(5)?.as_bytes()?.to_vec();
     let encrypted_value: Vec<u8> = row.get(6)?;
     if encrypted_value.is_empty() {
       continue;
     }
-    let decrypted_value = decrypt_encrypted_value(value, &encrypted_value, keys.to_owned())?;
+    let encrypted = value.is_empty() && is_encrypted(&encrypted_value);
+    let domain_hash = hashed_domains.then(|| Sha256::digest(host_key.as_bytes()));
+    let decrypted = decrypt_encrypted_value(
+      platform,
+      value,
+      &encrypted_value,
+      keys,
+      domain_hash.as_deref(),
+    );
+    let (plaintext, decryption) = match decrypted {
+      Ok(plaintext) if encrypted && hashed_domains => strip_domain_hash(&host_key, plaintext),
+      Ok(plaintext) => (plaintext, DecryptionStatus::Ok),
+      Err(e) if mode == DecryptionMode::Lenient => {
+        log::debug!("Can't decrypt {} of {}: {}", name, host_key, e);
+        undecoded += 1;
+        (vec![], DecryptionStatus::Failed(e.to_string()))
+      }
+      Err(e) => return Err(e),
+    };
+    let (decrypted_value, value_bytes) = decode_value(plaintext);
+    let decryption = match (value_bytes.is_some(), decryption) {
+      (true, DecryptionStatus::Ok) => DecryptionStatus::Lossy,
+      (_, decryption) => decryption,
+    };
     let http_only: bool = row.get(7)?;
 
     let same_site: i64 = row.get(8)?;
+    let timestamp = |index: usize| -> Result<Option<u64>> {
+      let timestamp: Option<u64> = row.get(index)?;
+      Ok(timestamp.and_then(date::chromium_timestamp))
+    };
     let cookie = Cookie {
       domain: host_key.to_string(),
       path: path.to_string(),
@@ -347,10 +597,157 @@ fn query_cookies(
       expires,
       name: name.to_string(),
       value: decrypted_value,
+      value_bytes,
       http_only,
-      same_site,
+      same_site: SameSite::from_chromium(same_site),
+      same_site_raw: Some(same_site),
+      creation: timestamp(9)?,
+      last_accessed: timestamp(10)?,
+      last_update: timestamp(11)?,
+      priority: row.get(12)?,
+      source_scheme: row.get(13)?,
+      source_port: row.get(14)?,
+      is_persistent: row.get(15)?,
+      decryption,
+      ..Default::default()
     };
     cookies.push(cookie);
   }
-  Ok(cookies)
+  Ok((cookies, undecoded))
+}
+
+/// Display name and account of a profile in `Local State`
+pub(crate) struct ProfileInfo {
+  pub name: Option<String>,
+  pub email: Option<String>,
+}
+
+/// Returns `profile.info_cache` entries by profile directory, along with the last used profile
+pub(crate) fn read_profile_info_cache(
+  local_state: &Path,
+) -> Result<(HashMap<String, ProfileInfo>, Option<String>)> {
+  let content = std::fs::read_to_string(local_state)?;
+  let json: serde_json::Value = serde_json::from_str(&content)?;
+  let not_empty = |value: &serde_json::Value| {
+    value
+      .as_str()
+      .filter(|value| !value.is_empty())
+      .map(String::from)
+  };
+  let mut profiles = HashMap::new();
+  if let Some(info_cache) = json["profile"]["info_cache"].as_object() {
+    for (dir, info) in info_cache {
+      let info = ProfileInfo {
+        name: not_empty(&info["name"]),
+        email: not_empty(&info["user_name"]),
+      };
+      profiles.insert(dir.to_string(), info);
+    }
+  }
+  Ok((profiles, not_empty(&json["profile"]["last_used"])))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("tests/fixtures/cookies")
+      .join(name)
+  }
+
+  #[test]
+  fn decrypts_macos_profile() {
+    let source = ChromiumKeySource::Password("macpass".to_string());
+    let cookies =
+      chromium_based_offline(ChromiumPlatform::MacOs, source, fixture("mac.db"), None).unwrap();
+    assert_eq!(cookies.len(), 1);
+    assert_eq!(cookies[0].domain, ".github.com");
+    assert_eq!(cookies[0].value, "hello");
+    assert_eq!(cookies[0].decryption, DecryptionStatus::Ok);
+  }
+
+  #[test]
+  fn decrypts_windows_profile() {
+    // Key of `Local State` once unwrapped with DPAPI
+    let source = ChromiumKeySource::Key((0..32).collect());
+    let cookies =
+      chromium_based_offline(ChromiumPlatform::Windows, source, fixture("win.db"), None).unwrap();
+    assert_eq!(cookies.len(), 1);
+    assert_eq!(cookies[0].value, "hello");
+    assert_eq!(cookies[0].decryption, DecryptionStatus::Ok);
+  }
+
+  #[test]
+  fn skips_wrong_key_with_valid_padding() {
+    // Encrypted with the `peanuts` key, the key of the stale password comes first and yields a
+    // valid padding
+    for (name, value) in [("linux.db", "hello-242"), ("linux-v23.db", "hello-49")] {
+      let source = ChromiumKeySource::Password("stale".to_string());
+      let cookies =
+        chromium_based_offline(ChromiumPlatform::Linux, source, fixture(name), None).unwrap();
+      assert_eq!(cookies[0].value, value, "{}", name);
+      assert_eq!(cookies[0].decryption, DecryptionStatus::Ok, "{}", name);
+    }
+  }
+
+  #[test]
+  fn rejects_wrong_keys() {
+    let source = ChromiumKeySource::Password("wrong".to_string());
+    let result = chromium_based_offline(ChromiumPlatform::MacOs, source, fixture("mac.db"), None);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+
+    // Right password, wrong number of PBKDF2 iterations
+    let source = ChromiumKeySource::Password("macpass".to_string());
+    let result = chromium_based_offline(ChromiumPlatform::Linux, source, fixture("mac.db"), None);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+
+    let source = ChromiumKeySource::Key(vec![0; 32]);
+    let result = chromium_based_offline(ChromiumPlatform::Windows, source, fixture("win.db"), None);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+  }
+
+  #[test]
+  fn prefilters_like_in_memory() {
+    use crate::common::domain::tests::{filters, matching_hosts, HOSTS};
+    use rusqlite::Connection;
+
+    let dir = std::env::temp_dir().join(format!("rookie-chromium-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("Cookies");
+    let connection = Connection::open(&db_path).unwrap();
+    connection
+      .execute(
+        "CREATE TABLE cookies (host_key TEXT, path TEXT, is_secure INTEGER, expires_utc INTEGER, \
+         name TEXT, value TEXT, encrypted_value BLOB, is_httponly INTEGER, samesite INTEGER)",
+        [],
+      )
+      .unwrap();
+    for host in HOSTS {
+      connection
+        .execute(
+          "INSERT INTO cookies VALUES (?, '/', 0, 0, 'name', 'value', x'00', 0, 0)",
+          [host],
+        )
+        .unwrap();
+    }
+    drop(connection);
+
+    for filter in filters() {
+      let (cookies, _) = query_cookies(
+        ChromiumPlatform::Linux,
+        &[],
+        db_path.clone(),
+        &filter,
+        DecryptionMode::Strict,
+        &Deadline::default(),
+      )
+      .unwrap();
+      let mut hosts: Vec<String> = cookies.into_iter().map(|cookie| cookie.domain).collect();
+      hosts.sort();
+      assert_eq!(hosts, matching_hosts(&filter), "{:?}", filter);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+  }
 }
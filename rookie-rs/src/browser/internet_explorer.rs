@@ -1,4 +1,9 @@
-use crate::common::{date, enums::Cookie};
+use crate::common::{
+  cancel::Deadline,
+  date,
+  domain::DomainFilter,
+  enums::{decode_value, Cookie},
+};
 use eyre::Result;
 use libesedb::EseDb;
 use std::path::PathBuf;
@@ -7,11 +12,27 @@ use std::path::PathBuf;
 pub fn internet_explorer_based(
   db_path: PathBuf,
   domains: Option<Vec<String>>,
+) -> crate::Result<Vec<Cookie>> {
+  internet_explorer_based_filtered(db_path, &domains.into(), &Deadline::default())
+}
+
+/// Same as [`internet_explorer_based`] with a domain filter of any mode, the file is unlocked
+/// within the deadline
+pub(crate) fn internet_explorer_based_filtered(
+  db_path: PathBuf,
+  filter: &DomainFilter,
+  deadline: &Deadline,
+) -> crate::Result<Vec<Cookie>> {
+  Ok(read_cookies(db_path, filter, deadline)?)
+}
+
+fn read_cookies(
+  db_path: PathBuf,
+  filter: &DomainFilter,
+  deadline: &Deadline,
 ) -> Result<Vec<Cookie>> {
-  unsafe {
-    if let Some(path) = db_path.to_str() {
-      crate::windows::restart_manager::release_file_lock(path);
-    }
+  if let Some(path) = db_path.to_str() {
+    crate::windows::restart_manager::release_file_lock_within(path, deadline)?;
   }
   let db = EseDb::open(db_path)?;
   let mut cookies: Vec<Cookie> = vec![];
@@ -33,19 +54,23 @@ pub fn internet_explorer_based(
           .trim_matches('\0')
           .to_string();
         let value = rec.value(11)?;
-        let same_site = 0;
-        let value = String::from_utf8(value.as_bytes().unwrap_or(&[]).to_vec())
-          .unwrap_or("".to_string())
-          .trim_matches('\0')
-          .to_string();
+        let value = value.as_bytes().unwrap_or(&[]);
+        // Trim null bytes on both ends
+        let start = value
+          .iter()
+          .position(|&byte| byte != 0)
+          .unwrap_or(value.len());
+        let end = value
+          .iter()
+          .rposition(|&byte| byte != 0)
+          .map_or(start, |end| end + 1);
+        let (value, value_bytes) = decode_value(value[start..end].to_vec());
         let secure = false;
         let expires = rec.value(4)?.to_u64().unwrap_or(0);
         let expires = date::internet_explorer_timestamp(expires);
         let http_only = false;
 
-        let should_append =
-          domains.is_none() || domains.iter().any(|d| d.contains(&host.to_string()));
-        if should_append {
+        if filter.matches(host) {
           cookies.push(Cookie {
             domain: host.to_string(),
             path: path.to_string(),
@@ -53,8 +78,9 @@ pub fn internet_explorer_based(
             expires,
             name,
             value,
+            value_bytes,
             http_only,
-            same_site,
+            ..Default::default()
           })
         }
       }
@@ -1,7 +1,8 @@
-use crate::common::{date, enums::*, sqlite, utils};
+use crate::common::{date, domain::DomainFilter, enums::*, sqlite};
 use eyre::{anyhow, bail, Result};
 use ini::Ini;
 use lz4_flex::block::decompress_size_prepended;
+use rusqlite::params_from_iter;
 use serde_json::Value;
 use std::{
   fs,
@@ -9,30 +10,43 @@ use std::{
 };
 
 /// Returns cookies from mozilla based browsers
-pub fn firefox_based(db_path: PathBuf, domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
+pub fn firefox_based(db_path: PathBuf, domains: Option<Vec<String>>) -> crate::Result<Vec<Cookie>> {
+  firefox_based_filtered(db_path, &domains.into())
+}
+
+/// Same as [`firefox_based`] with a domain filter of any mode
+pub(crate) fn firefox_based_filtered(
+  db_path: PathBuf,
+  filter: &DomainFilter,
+) -> crate::Result<Vec<Cookie>> {
+  Ok(query_cookies(db_path, filter)?)
+}
+
+fn query_cookies(db_path: PathBuf, filter: &DomainFilter) -> Result<Vec<Cookie>> {
   let connection = sqlite::connect(db_path.clone())?;
-  let mut query = "
-        SELECT host, path, isSecure, expiry, name, value, isHttpOnly, sameSite from moz_cookies 
+  let optional_columns = ["creationTime", "lastAccessed", "schemeMap", "rawSameSite"]
+    .iter()
+    .map(|column| sqlite::optional_column(&connection, "moz_cookies", column))
+    .collect::<Result<Vec<String>>>()?;
+  let mut query = format!(
     "
-  .to_string();
+        SELECT host, path, isSecure, expiry, name, value, isHttpOnly, sameSite, {} from moz_cookies 
+    ",
+    optional_columns.join(", ")
+  );
 
-  if let Some(domains) = domains.clone() {
-    let domain_queries: Vec<String> = domains
-      .iter()
-      .map(|domain| format!("host LIKE '%{}%'", domain))
-      .collect();
-
-    if !domain_queries.is_empty() {
-      let joined_queries = domain_queries.join(" OR ");
-      query += &format!("WHERE ({})", joined_queries);
-    }
+  let patterns = filter.like_patterns().unwrap_or_default();
+  if !patterns.is_empty() {
+    query += &sqlite::like_clause("host", &patterns);
   }
 
   query += ";";
 
   let mut cookies: Vec<Cookie> = vec![];
-  let mut stmt = connection.prepare(query.as_str())?;
-  let mut rows = stmt.query([])?;
+  let mut stmt = connection
+    .prepare(query.as_str())
+    .map_err(|e| sqlite::map_error(&db_path, e))?;
+  let mut rows = stmt.query(params_from_iter(&patterns))?;
 
   while let Some(row) = rows.next()? {
     let host: Result<String, _> = row.get(0);
@@ -42,17 +56,24 @@ pub fn firefox_based(db_path: PathBuf, domains: Option<Vec<String>>) -> Result<V
       continue;
     }
     let host = host?;
+    if !filter.matches(&host) {
+      continue;
+    }
     let path: String = row.get(1)?;
     let is_secure: bool = row.get(2)?;
-    let expires: u64 = row.get(3)?;
-    let expires = date::mozilla_timestamp(expires);
+    let expiry: u64 = row.get(3)?;
+    let expires = date::mozilla_timestamp(expiry);
+    // Session cookies expire at the largest timestamp, when Firefox keeps them in the database
+    let is_persistent = expiry != 0 && expiry < i64::MAX as u64;
 
     let name: String = row.get(4)?;
 
-    let value: String = row.get(5)?;
+    let (value, value_bytes) = decode_value(row.get_ref(5)?.as_bytes()?.to_vec());
     let http_only: bool = row.get(6)?;
 
     let same_site: i64 = row.get(7)?;
+    let creation: Option<u64> = row.get(8)?;
+    let last_accessed: Option<u64> = row.get(9)?;
     let cookie = Cookie {
       domain: host.to_string(),
       path: path.to_string(),
@@ -60,27 +81,31 @@ pub fn firefox_based(db_path: PathBuf, domains: Option<Vec<String>>) -> Result<V
       expires,
       name: name.to_string(),
       value,
+      value_bytes,
       http_only,
-      same_site,
+      same_site: SameSite::from_firefox(same_site, row.get(11)?),
+      same_site_raw: Some(same_site),
+      creation: creation.and_then(date::mozilla_micros_timestamp),
+      last_accessed: last_accessed.and_then(date::mozilla_micros_timestamp),
+      scheme_map: row.get(10)?,
+      is_persistent: Some(is_persistent),
+      ..Default::default()
     };
     cookies.push(cookie);
   }
 
   let parent_path = db_path.parent().unwrap_or(&PathBuf::from("")).to_path_buf();
-  if let Ok(session_cookies) = get_session_cookies_lz4(domains.to_owned(), parent_path.to_owned()) {
+  if let Ok(session_cookies) = get_session_cookies_lz4(filter, parent_path.to_owned()) {
     cookies.extend(session_cookies);
   }
 
-  if let Ok(session_cookies) = get_session_cookies(domains, parent_path) {
+  if let Ok(session_cookies) = get_session_cookies(filter, parent_path) {
     cookies.extend(session_cookies);
   }
   Ok(cookies)
 }
 
-pub fn get_session_cookies(
-  domains: Option<Vec<String>>,
-  cookies_dir: PathBuf,
-) -> Result<Vec<Cookie>> {
+pub fn get_session_cookies(filter: &DomainFilter, cookies_dir: PathBuf) -> Result<Vec<Cookie>> {
   let mut cookies: Vec<Cookie> = vec![];
   let session_file = cookies_dir.join("sessionstore.js");
   let plain = fs::read_to_string(session_file)?;
@@ -100,9 +125,7 @@ pub fn get_session_cookies(
             .get("host")
             .and_then(|v| v.as_str())
             .unwrap_or("");
-          let should_add = domains.is_none() || // add every domain
-                        domains.is_some() && utils::some_domain_in_host(domains.to_owned(), domain); // only if some domain in host
-          if !should_add {
+          if !filter.matches(domain) {
             continue;
           }
           if let Ok(cookie) = create_cookie(json_cookie) {
@@ -115,10 +138,7 @@ pub fn get_session_cookies(
   Ok(cookies)
 }
 
-pub fn get_session_cookies_lz4(
-  domains: Option<Vec<String>>,
-  cookies_dir: PathBuf,
-) -> Result<Vec<Cookie>> {
+pub fn get_session_cookies_lz4(filter: &DomainFilter, cookies_dir: PathBuf) -> Result<Vec<Cookie>> {
   let mut cookies: Vec<Cookie> = vec![];
   let session_file_lz4 = cookies_dir.join("sessionstore-backups/recovery.jsonlz4");
   let compressed = fs::read(session_file_lz4)?;
@@ -135,9 +155,7 @@ pub fn get_session_cookies_lz4(
       .get("host")
       .and_then(|v| v.as_str())
       .unwrap_or("");
-    let should_add = domains.is_none() || // add every domain
-                        utils::some_domain_in_host(domains.to_owned(), domain); // only if some domain in host
-    if !should_add {
+    if !filter.matches(domain) {
       continue;
     }
     if let Ok(cookie) = create_cookie(json_cookie) {
@@ -190,8 +208,12 @@ pub fn create_cookie(json_cookie: &Value) -> Result<Cookie> {
     name: name.to_string(),
     value: value.to_string(),
     path: path.to_string(),
-    same_site,
+    same_site: SameSite::from_firefox(same_site, None),
+    same_site_raw: Some(same_site),
     secure,
+    // Session store only keeps session cookies
+    is_persistent: Some(false),
+    ..Default::default()
   };
   Ok(cookie)
 }
@@ -225,3 +247,112 @@ pub fn get_default_profile(profiles_path: &Path) -> Result<String> {
   }
   bail!("Can't find any profile")
 }
+
+/// Returns the paths of all profiles listed in profiles.ini
+pub fn get_profile_paths(profiles_path: &Path) -> Result<Vec<String>> {
+  let conf = Ini::load_from_file(profiles_path)?;
+  let paths = conf
+    .iter()
+    .filter(|(name_option, _)| name_option.unwrap_or_default().starts_with("Profile"))
+    .filter_map(|(_, props)| props.get("Path"))
+    .map(|path| path.to_string())
+    .collect();
+  Ok(paths)
+}
+
+/// Profile listed in profiles.ini
+pub(crate) struct IniProfile {
+  pub name: Option<String>,
+  pub path: PathBuf,
+  pub is_default: bool,
+}
+
+/// Returns every profile of profiles.ini with defaults taken from its install sections and installs.ini
+pub(crate) fn read_profiles_ini(base_dir: &Path) -> Result<Vec<IniProfile>> {
+  let conf = Ini::load_from_file(base_dir.join("profiles.ini"))?;
+  let mut install_defaults: Vec<String> = conf
+    .iter()
+    .filter(|(name_option, _)| name_option.unwrap_or_default().starts_with("Install"))
+    .filter_map(|(_, props)| props.get("Default").map(String::from))
+    .collect();
+  if let Ok(installs) = Ini::load_from_file(base_dir.join("installs.ini")) {
+    install_defaults.extend(
+      installs
+        .iter()
+        .filter_map(|(_, props)| props.get("Default").map(String::from)),
+    );
+  }
+
+  let profiles = conf
+    .iter()
+    .filter(|(name_option, _)| name_option.unwrap_or_default().starts_with("Profile"))
+    .filter_map(|(_, props)| {
+      let path = props.get("Path")?;
+      let is_default = if install_defaults.is_empty() {
+        props.get("Default") == Some("1")
+      } else {
+        install_defaults.iter().any(|default| default == path)
+      };
+      let path = if props.get("IsRelative") == Some("0") {
+        PathBuf::from(path)
+      } else {
+        base_dir.join(path)
+      };
+      Some(IniProfile {
+        name: props.get("Name").map(String::from),
+        path,
+        is_default,
+      })
+    })
+    .collect();
+  Ok(profiles)
+}
+
+/// Returns the e-mail of the account signed in a profile
+pub(crate) fn read_account_email(profile_dir: &Path) -> Option<String> {
+  let content = fs::read_to_string(profile_dir.join("signedInUser.json")).ok()?;
+  let json: Value = serde_json::from_str(&content).ok()?;
+  json["accountData"]["email"].as_str().map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::domain::tests::{filters, matching_hosts, HOSTS};
+  use rusqlite::Connection;
+
+  #[test]
+  fn prefilters_like_in_memory() {
+    let dir = std::env::temp_dir().join(format!("rookie-mozilla-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("cookies.sqlite");
+    let connection = Connection::open(&db_path).unwrap();
+    connection
+      .execute(
+        "CREATE TABLE moz_cookies (host TEXT, path TEXT, isSecure INTEGER, expiry INTEGER, \
+         name TEXT, value TEXT, isHttpOnly INTEGER, sameSite INTEGER)",
+        [],
+      )
+      .unwrap();
+    for host in HOSTS {
+      connection
+        .execute(
+          "INSERT INTO moz_cookies VALUES (?, '/', 0, 0, 'name', 'value', 0, 0)",
+          [host],
+        )
+        .unwrap();
+    }
+    drop(connection);
+
+    for filter in filters() {
+      let mut hosts: Vec<String> = query_cookies(db_path.clone(), &filter)
+        .unwrap()
+        .into_iter()
+        .map(|cookie| cookie.domain)
+        .collect();
+      hosts.sort();
+      assert_eq!(hosts, matching_hosts(&filter), "{:?}", filter);
+    }
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -1,7 +1,13 @@
-use crate::common::{date, enums::*};
+use crate::common::{date, domain::DomainFilter, enums::*};
+use crate::RookieError;
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
-use eyre::{anyhow, bail, Context, Result};
-use std::{fs::File, io::Read, path::PathBuf, vec::Vec};
+use eyre::{anyhow, bail, Result};
+use std::{
+  fs::File,
+  io::{ErrorKind, Read},
+  path::PathBuf,
+  vec::Vec,
+};
 
 /// 1. open cookies file
 /// 2. parse headers
@@ -9,42 +15,50 @@ use std::{fs::File, io::Read, path::PathBuf, vec::Vec};
 /// 4. get N cookies from each page, iterate
 /// 5. parse each cookie
 /// 6. add each cookie based on domain filter
-pub fn safari_based(db_path: PathBuf, domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let mut file = File::open(&db_path).context(format!(
-    "Failed to open {}\n\
-      Make sure you have full disk access for the current process.\n\
-      For example, in VSCode or Terminal:\n\
-      1. Open Settings\n\
-      2. Privacy & Security\n\
-      3. Full Disk Access\n\
-      ---\n\
-      You can also open the disk access page with: \n\
-      open \"x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles\"\n\
-      ",
-    db_path.display()
-  ))?;
+pub fn safari_based(db_path: PathBuf, domains: Option<Vec<String>>) -> crate::Result<Vec<Cookie>> {
+  safari_based_filtered(db_path, &domains.into())
+}
+
+/// Same as [`safari_based`] with a domain filter of any mode
+pub(crate) fn safari_based_filtered(
+  db_path: PathBuf,
+  filter: &DomainFilter,
+) -> crate::Result<Vec<Cookie>> {
+  let file = File::open(&db_path).map_err(|e| match e.kind() {
+    ErrorKind::PermissionDenied => RookieError::PermissionDenied {
+      path: db_path.clone(),
+      hint: Some(
+        "Make sure you have full disk access for the current process.\n\
+        For example, in VSCode or Terminal:\n\
+        1. Open Settings\n\
+        2. Privacy & Security\n\
+        3. Full Disk Access\n\
+        ---\n\
+        You can also open the disk access page with: \n\
+        open \"x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles\"\n\
+        "
+        .to_string(),
+      ),
+      source: e,
+    },
+    _ => eyre::Report::new(e)
+      .wrap_err(format!("Failed to open {}", db_path.display()))
+      .into(),
+  })?;
+  Ok(read_cookies(file, filter)?)
+}
+
+fn read_cookies(mut file: File, filter: &DomainFilter) -> Result<Vec<Cookie>> {
   let mut bs: Vec<u8> = Vec::new();
   file.read_to_end(&mut bs)?;
   let cookies = parse_content(&bs)?;
 
-  // Filter cookies by domain if domains are specified
-  if let Some(domain_filters) = domains {
-    let filtered_cookies: Vec<Cookie> = cookies
+  Ok(
+    cookies
       .into_iter()
-      .filter(|cookie| {
-        // Check if the cookie's domain matches any of the specified domains
-        domain_filters.iter().any(|domain| {
-          // Implement your domain matching logic here
-          // For example, you can use the `.ends_with` method to check if the cookie's domain ends with the specified domain.
-          cookie.domain.ends_with(domain)
-        })
-      })
-      .collect();
-
-    Ok(filtered_cookies)
-  } else {
-    Ok(cookies)
-  }
+      .filter(|cookie| filter.matches(&cookie.domain))
+      .collect(),
+  )
 }
 
 fn parse_page(bs: &[u8]) -> Result<Vec<Cookie>> {
@@ -91,11 +105,16 @@ fn parse_cookie<T: ByteOrder>(bs: &[u8]) -> Result<Cookie> {
   // i/OS/X to Unix timestamp +(1 Jan 2001 epoch seconds).
   let expires = T::read_u64(&bs[0x28..0x30]);
   let expires = date::safari_timestamp(expires);
+  let creation = bs
+    .get(0x30..0x38)
+    .and_then(|creation| date::safari_float_timestamp(T::read_f64(creation)));
 
   let url = slice_to(bs, url_off, name_off).and_then(c_str)?;
   let name = slice_to(bs, name_off, path_off).and_then(c_str)?;
   let path = slice_to(bs, path_off, value_off).and_then(c_str)?;
-  let value = slice_to(bs, value_off, bs.len()).and_then(c_str)?;
+  // Values may hold any bytes
+  let value = slice_to(bs, value_off, bs.len()).and_then(c_bytes)?;
+  let (value, value_bytes) = decode_value(value.to_vec());
 
   let is_secure = (flags & 0x01) == 0x01;
   let is_http_only = (flags & 0x04) == 0x04;
@@ -107,8 +126,10 @@ fn parse_cookie<T: ByteOrder>(bs: &[u8]) -> Result<Cookie> {
     name,
     path,
     value,
-    same_site: 0,
+    value_bytes,
     secure: is_secure,
+    creation,
+    ..Default::default()
   };
   Ok(cookie)
 }
@@ -116,7 +137,7 @@ fn parse_cookie<T: ByteOrder>(bs: &[u8]) -> Result<Cookie> {
 fn parse_content(bs: &[u8]) -> Result<Vec<Cookie>> {
   // Magic bytes: "COOK" = 0x636F6F6B
   if slice(bs, 0, 4)? != [0x63, 0x6f, 0x6f, 0x6b] {
-    bail!("not a cookie file");
+    return Err(RookieError::UnsupportedSchema("not a cookie file".to_string()).into());
   }
 
   let count = slice(bs, 4, 4).map(BigEndian::read_u32)? as usize;
@@ -174,7 +195,8 @@ fn slice_to(bs: &[u8], off: usize, to: usize) -> Result<&[u8]> {
   }
 }
 
-fn c_str(bs: &[u8]) -> Result<String> {
+/// Returns the bytes of a null terminated string, without the terminator
+fn c_bytes(bs: &[u8]) -> Result<&[u8]> {
   bs.split_last()
     .ok_or_else(|| anyhow!("null c string"))
     .and_then(|(&last, elements)| {
@@ -184,7 +206,10 @@ fn c_str(bs: &[u8]) -> Result<String> {
         bail!("c string non null terminator")
       }
     })
-    .and_then(|elements| {
-      String::from_utf8(elements.to_vec()).map_err(|err| anyhow!(err.to_string()))
-    })
+}
+
+fn c_str(bs: &[u8]) -> Result<String> {
+  c_bytes(bs).and_then(|elements| {
+    String::from_utf8(elements.to_vec()).map_err(|err| anyhow!(err.to_string()))
+  })
 }
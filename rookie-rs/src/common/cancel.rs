@@ -0,0 +1,292 @@
+use crate::RookieError;
+use eyre::{bail, Result};
+use std::{
+  cell::RefCell,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, RecvTimeoutError},
+    Arc,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+/// How often a waiting operation checks whether it was cancelled
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Handle cancelling blocking operations from another thread, clones cancel the same operations
+///
+/// # Examples
+///
+/// ```
+/// use rookie::{common::cancel::CancelHandle, CookieQuery};
+///
+/// let cancel = CancelHandle::new();
+/// let query = CookieQuery::new().browser("chrome").cancel_handle(cancel.clone());
+/// // From the UI thread, when the user gives up
+/// cancel.cancel();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cancels the operations waiting on this handle, and the next ones
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+thread_local! {
+  /// Triggered when [`Deadline::run`] stops waiting for the operation running on this thread
+  static ABANDONED: RefCell<Option<CancelHandle>> = const { RefCell::new(None) };
+}
+
+/// Returns true if the operation running on this thread timed out or was cancelled, long waits
+/// check it to stop early, such as by dismissing the prompt they wait for
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) fn is_abandoned() -> bool {
+  ABANDONED.with(|abandoned| {
+    abandoned
+      .borrow()
+      .as_ref()
+      .is_some_and(CancelHandle::is_cancelled)
+  })
+}
+
+/// Timeout and cancellation of operations which may block, such as a keyring waiting for the
+/// user to unlock it
+///
+/// Once [started](Deadline::start), the timeout bounds every operation run within the deadline
+/// together. An operation which doesn't finish in time is told to stop: the Secret Service prompt is
+/// dismissed and the restart manager stops restarting applications. A call which can't be
+/// interrupted, such as the KWallet `open` showing its unlock dialog, keeps its thread until it
+/// returns, its result is then dropped.
+#[derive(Debug, Clone, Default)]
+pub struct Deadline {
+  pub timeout: Option<Duration>,
+  pub cancel: Option<CancelHandle>,
+  /// When the timeout of a started deadline runs out
+  expires: Option<Instant>,
+}
+
+impl Deadline {
+  /// Returns true if neither a timeout nor a cancel handle is set
+  pub fn is_unlimited(&self) -> bool {
+    self.timeout.is_none() && self.cancel.is_none()
+  }
+
+  /// Starts the timeout, the operations run from now on share it, a started deadline is kept as is
+  pub(crate) fn start(&self) -> Self {
+    let mut deadline = self.clone();
+    if deadline.expires.is_none() {
+      deadline.expires = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+    deadline
+  }
+
+  /// Fails with [`RookieError::Cancelled`] if the cancel handle was triggered, or with
+  /// [`RookieError::Timeout`] if the started timeout ran out
+  pub(crate) fn check(&self, operation: &str) -> Result<()> {
+    match self.failure(operation, self.expires) {
+      Some(error) => Err(error.into()),
+      None => Ok(()),
+    }
+  }
+
+  fn failure(&self, operation: &str, expires: Option<Instant>) -> Option<RookieError> {
+    if self.cancel.as_ref().is_some_and(CancelHandle::is_cancelled) {
+      return Some(RookieError::Cancelled(operation.to_string()));
+    }
+    if expires.is_some_and(|expires| Instant::now() >= expires) {
+      return Some(RookieError::Timeout {
+        operation: operation.to_string(),
+        timeout: self.timeout.unwrap_or_default(),
+      });
+    }
+    None
+  }
+
+  /// Runs an operation, failing with [`RookieError::Timeout`] or [`RookieError::Cancelled`] when
+  /// it doesn't finish in time
+  pub(crate) fn run<T, F>(&self, operation: &str, f: F) -> Result<T>
+  where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+  {
+    self.run_or_abort(operation, f, || {})
+  }
+
+  /// Same as [`Deadline::run`], `abort` is called from the waiting thread to stop an operation
+  /// which doesn't finish in time
+  pub(crate) fn run_or_abort<T, F, A>(&self, operation: &str, f: F, abort: A) -> Result<T>
+  where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+    A: FnOnce(),
+  {
+    if self.is_unlimited() {
+      return f();
+    }
+    self.check(operation)?;
+    let expires = self
+      .expires
+      .or_else(|| Some(Instant::now() + self.timeout?));
+    let abandoned = CancelHandle::new();
+    let (sender, receiver) = mpsc::channel();
+    {
+      let abandoned = abandoned.clone();
+      thread::spawn(move || {
+        ABANDONED.with(|cell| *cell.borrow_mut() = Some(abandoned));
+        let _ = sender.send(f());
+      });
+    }
+    loop {
+      let wait = match expires {
+        Some(expires) => expires
+          .saturating_duration_since(Instant::now())
+          .min(POLL_INTERVAL),
+        None => POLL_INTERVAL,
+      };
+      match receiver.recv_timeout(wait) {
+        Ok(result) => return result,
+        Err(RecvTimeoutError::Disconnected) => bail!("{} panicked", operation),
+        Err(RecvTimeoutError::Timeout) => {
+          if let Some(error) = self.failure(operation, expires) {
+            log::debug!("Stopping {}: {}", operation, error);
+            abandoned.cancel();
+            abort();
+            return Err(error.into());
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Operation blocking until it's released, returning whether it was abandoned
+  fn blocking() -> (
+    mpsc::Sender<()>,
+    impl FnOnce() -> Result<bool> + Send + 'static,
+  ) {
+    let (release, released) = mpsc::channel();
+    let operation = move || {
+      let _ = released.recv();
+      Ok(is_abandoned())
+    };
+    (release, operation)
+  }
+
+  #[test]
+  fn runs_unlimited_operation_in_place() {
+    let deadline = Deadline::default();
+    assert!(deadline.is_unlimited());
+    let thread = thread::current().id();
+    assert!(deadline
+      .run("test", move || Ok(thread::current().id() == thread))
+      .unwrap());
+  }
+
+  #[test]
+  fn times_out() {
+    let deadline = Deadline {
+      timeout: Some(Duration::from_millis(100)),
+      ..Default::default()
+    };
+    let (release, operation) = blocking();
+    let started = Instant::now();
+    let mut aborted = false;
+    let result = deadline.run_or_abort("test", operation, || aborted = true);
+    assert!(matches!(
+      result.map_err(RookieError::from),
+      Err(RookieError::Timeout { .. })
+    ));
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(aborted);
+    drop(release);
+  }
+
+  #[test]
+  fn is_cancelled_from_another_thread() {
+    let cancel = CancelHandle::new();
+    let deadline = Deadline {
+      cancel: Some(cancel.clone()),
+      ..Default::default()
+    };
+    let (release, operation) = blocking();
+    let canceller = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(100));
+      let started = Instant::now();
+      cancel.cancel();
+      started
+    });
+    let result = deadline.run("test", operation);
+    let cancelled = canceller.join().unwrap();
+    assert!(matches!(
+      result.map_err(RookieError::from),
+      Err(RookieError::Cancelled(_))
+    ));
+    // Noticed at the next poll
+    assert!(cancelled.elapsed() < POLL_INTERVAL * 10);
+    drop(release);
+
+    // Later operations fail right away, clones share the cancellation
+    assert!(deadline.check("test").is_err());
+    assert!(deadline.run("test", || Ok(())).is_err());
+  }
+
+  #[test]
+  fn tells_abandoned_operation() {
+    let deadline = Deadline {
+      timeout: Some(Duration::from_millis(50)),
+      ..Default::default()
+    };
+    let (abandoned, was_abandoned) = mpsc::channel();
+    let result = deadline.run("test", move || {
+      while !is_abandoned() {
+        thread::sleep(Duration::from_millis(10));
+      }
+      let _ = abandoned.send(());
+      Ok(())
+    });
+    assert!(result.is_err());
+    assert!(was_abandoned.recv_timeout(Duration::from_secs(2)).is_ok());
+    assert!(!is_abandoned());
+
+    let (release, operation) = blocking();
+    release.send(()).unwrap();
+    assert!(!deadline.run("test", operation).unwrap());
+  }
+
+  #[test]
+  fn shares_started_timeout() {
+    let deadline = Deadline {
+      timeout: Some(Duration::from_millis(150)),
+      ..Default::default()
+    }
+    .start();
+    // Each operation is within the timeout, not both of them
+    let sleep = || {
+      thread::sleep(Duration::from_millis(100));
+      Ok(())
+    };
+    assert!(deadline.run("first", sleep).is_ok());
+    let result = deadline.run("second", sleep).map_err(RookieError::from);
+    assert!(matches!(result, Err(RookieError::Timeout { .. })));
+    assert!(deadline.check("third").is_err());
+    // Starting again keeps the expiry
+    assert!(deadline.start().check("fourth").is_err());
+  }
+}
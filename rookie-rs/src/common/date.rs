@@ -7,6 +7,11 @@ pub fn chromium_timestamp(timestamp: u64) -> Option<u64> {
   unix_timestamp(timestamp)
 }
 
+/// Converts mozilla `PRTime` microseconds, used by creation and access times
+pub fn mozilla_micros_timestamp(timestamp: u64) -> Option<u64> {
+  unix_timestamp(timestamp / 1_000_000)
+}
+
 pub fn mozilla_timestamp(timestamp: u64) -> Option<u64> {
   unix_timestamp(timestamp)
 }
@@ -28,6 +33,15 @@ pub fn safari_timestamp(timestamp: u64) -> Option<u64> {
   let unix_timestamp = unix_timestamp / 1_000_000_000;
   Some(unix_timestamp)
 }
+/// Converts safari seconds since 2001 stored as float
+#[cfg(target_os = "macos")]
+pub fn safari_float_timestamp(timestamp: f64) -> Option<u64> {
+  if timestamp <= 0.0 {
+    return None;
+  }
+  Some(timestamp as u64 + 978_307_200)
+}
+
 #[cfg(target_os = "windows")]
 pub fn internet_explorer_timestamp(timestamp: u64) -> Option<u64> {
   if timestamp == 0 {
@@ -37,3 +51,11 @@ pub fn internet_explorer_timestamp(timestamp: u64) -> Option<u64> {
   timestamp /= 10_000_000;
   unix_timestamp(timestamp)
 }
+
+/// Returns current unix timestamp in seconds
+pub fn unix_now() -> u64 {
+  std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
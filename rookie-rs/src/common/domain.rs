@@ -0,0 +1,342 @@
+use crate::common::sqlite;
+use std::net::IpAddr;
+
+/// How cookie domains are compared against domain filters
+///
+/// # Examples
+///
+/// ```
+/// use rookie::common::domain::DomainMatch;
+///
+/// assert!(DomainMatch::Subdomains.matches(".api.github.com", "github.com"));
+/// assert!(!DomainMatch::Subdomains.matches("notgithub.com.evil", "github.com"));
+/// assert!(DomainMatch::Domain.matches(".github.com", "www.github.com"));
+/// assert!(!DomainMatch::Domain.matches("github.com", "www.github.com"));
+/// assert!(DomainMatch::Exact.matches(".github.com", "github.com"));
+/// assert!(DomainMatch::Substring.matches("notgithub.com.evil", "github.com"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DomainMatch {
+  /// Cookie domain equals the filter, ignoring a leading dot
+  Exact,
+  /// Cookie would be sent to the filter host, as defined by RFC 6265 domain-match
+  ///
+  /// `www.github.com` matches cookies of `.github.com` and host-only cookies of `www.github.com`.
+  Domain,
+  /// Cookie domain equals the filter or is a subdomain of it
+  #[default]
+  Subdomains,
+  /// Filter is contained anywhere in the cookie domain, so `github.com` matches `notgithub.com.evil`
+  Substring,
+}
+
+impl DomainMatch {
+  /// Returns true if the cookie domain matches the filter
+  pub fn matches(&self, cookie_domain: &str, filter: &str) -> bool {
+    let host_only = !cookie_domain.starts_with('.');
+    let cookie_domain = normalize(cookie_domain);
+    let filter = normalize(filter);
+    match self {
+      DomainMatch::Exact => cookie_domain == filter,
+      DomainMatch::Domain => {
+        filter == cookie_domain
+          || (!host_only
+            && filter.parse::<IpAddr>().is_err()
+            && is_subdomain(&filter, &cookie_domain))
+      }
+      DomainMatch::Subdomains => cookie_domain == filter || is_subdomain(&cookie_domain, &filter),
+      DomainMatch::Substring => cookie_domain.contains(&filter),
+    }
+  }
+
+  /// Returns true if the cookie domain matches any of the filters
+  pub fn matches_any(&self, cookie_domain: &str, filters: &[String]) -> bool {
+    filters
+      .iter()
+      .any(|filter| self.matches(cookie_domain, filter))
+  }
+}
+
+/// Domain filters along with how they're compared, used by every browser backend
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainFilter {
+  domains: Option<Vec<String>>,
+  mode: DomainMatch,
+}
+
+impl DomainFilter {
+  pub fn new(domains: Option<Vec<String>>, mode: DomainMatch) -> Self {
+    Self { domains, mode }
+  }
+
+  /// Returns true if there's no filter or the cookie domain matches any of them
+  pub fn matches(&self, cookie_domain: &str) -> bool {
+    match &self.domains {
+      Some(domains) if !domains.is_empty() => self.mode.matches_any(cookie_domain, domains),
+      _ => true,
+    }
+  }
+
+  /// Returns SQL `LIKE` patterns selecting a superset of the matching domains, escaped with `\`
+  ///
+  /// None means every domain may match.
+  pub(crate) fn like_patterns(&self) -> Option<Vec<String>> {
+    let domains = self
+      .domains
+      .as_ref()
+      .filter(|domains| !domains.is_empty())?;
+    let mut patterns = vec![];
+    for domain in domains {
+      let domain = sqlite::escape_like(&normalize(domain));
+      match self.mode {
+        DomainMatch::Exact | DomainMatch::Subdomains => patterns.push(format!("%{}", domain)),
+        DomainMatch::Domain => {
+          // Cookies can be set on the host or any of its parents
+          let mut suffix = domain.as_str();
+          loop {
+            patterns.push(suffix.to_string());
+            patterns.push(format!(".{}", suffix));
+            match suffix.split_once('.') {
+              Some((_, parent)) => suffix = parent,
+              None => break,
+            }
+          }
+        }
+        DomainMatch::Substring => patterns.push(format!("%{}%", domain)),
+      }
+    }
+    Some(patterns)
+  }
+}
+
+impl From<Option<Vec<String>>> for DomainFilter {
+  /// Filters with the default [`DomainMatch`]
+  fn from(domains: Option<Vec<String>>) -> Self {
+    Self::new(domains, DomainMatch::default())
+  }
+}
+
+fn normalize(domain: &str) -> String {
+  domain.trim_start_matches('.').to_lowercase()
+}
+
+/// Returns true if `domain` is a subdomain of `parent`, on a dot boundary
+fn is_subdomain(domain: &str, parent: &str) -> bool {
+  domain
+    .strip_suffix(parent)
+    .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use rusqlite::{params_from_iter, Connection};
+
+  /// Cookie domains which wildcards, case and dots could mix up
+  pub(crate) const HOSTS: [&str; 14] = [
+    "example.com",
+    ".example.com",
+    "www.example.com",
+    ".sub.example.com",
+    ".Example.COM",
+    "notexample.com",
+    "example.com.evil",
+    "example.co",
+    "a_b.com",
+    "axb.com",
+    "a%b.com",
+    "a%%b.com",
+    "192.168.0.1",
+    "localhost",
+  ];
+
+  /// Filters of every mode, including wildcards and a leading dot
+  pub(crate) fn filters() -> Vec<DomainFilter> {
+    let domains = [
+      vec!["example.com"],
+      vec![".EXAMPLE.com"],
+      vec!["www.example.com"],
+      vec!["sub.example.com"],
+      vec!["a_b.com"],
+      vec!["a%b.com"],
+      vec!["192.168.0.1"],
+      vec!["localhost"],
+      vec!["example.com", "a_b.com"],
+    ];
+    let modes = [
+      DomainMatch::Exact,
+      DomainMatch::Domain,
+      DomainMatch::Subdomains,
+      DomainMatch::Substring,
+    ];
+    modes
+      .iter()
+      .flat_map(|mode| {
+        domains.iter().map(|domains| {
+          let domains = domains.iter().map(|domain| domain.to_string()).collect();
+          DomainFilter::new(Some(domains), *mode)
+        })
+      })
+      .collect()
+  }
+
+  /// Returns the hosts matched in memory, sorted
+  pub(crate) fn matching_hosts(filter: &DomainFilter) -> Vec<String> {
+    let mut hosts: Vec<String> = HOSTS
+      .iter()
+      .filter(|host| filter.matches(host))
+      .map(|host| host.to_string())
+      .collect();
+    hosts.sort();
+    hosts
+  }
+
+  fn matches(mode: DomainMatch, cookie_domain: &str, filter: &str) -> bool {
+    mode.matches(cookie_domain, filter)
+  }
+
+  #[test]
+  fn matches_exact_domain() {
+    assert!(matches(DomainMatch::Exact, "github.com", "github.com"));
+    assert!(matches(DomainMatch::Exact, ".github.com", "github.com"));
+    assert!(matches(DomainMatch::Exact, "github.com", ".github.com"));
+    assert!(matches(DomainMatch::Exact, ".GitHub.com", "github.COM"));
+    assert!(!matches(DomainMatch::Exact, "api.github.com", "github.com"));
+    assert!(!matches(DomainMatch::Exact, "github.com", "api.github.com"));
+  }
+
+  #[test]
+  fn matches_cookies_sent_to_host() {
+    assert!(matches(DomainMatch::Domain, "github.com", "github.com"));
+    assert!(matches(DomainMatch::Domain, ".github.com", "github.com"));
+    assert!(matches(
+      DomainMatch::Domain,
+      ".github.com",
+      "api.github.com"
+    ));
+    assert!(matches(
+      DomainMatch::Domain,
+      ".GITHUB.com",
+      "Api.GitHub.com"
+    ));
+    // Host-only cookies aren't sent to subdomains
+    assert!(!matches(
+      DomainMatch::Domain,
+      "github.com",
+      "api.github.com"
+    ));
+    assert!(!matches(
+      DomainMatch::Domain,
+      ".api.github.com",
+      "github.com"
+    ));
+    assert!(!matches(
+      DomainMatch::Domain,
+      ".github.com",
+      "notgithub.com"
+    ));
+    // Nor are domain cookies sent to IP addresses
+    assert!(matches(DomainMatch::Domain, "192.168.0.1", "192.168.0.1"));
+    assert!(!matches(DomainMatch::Domain, ".0.1", "192.168.0.1"));
+  }
+
+  #[test]
+  fn matches_subdomains() {
+    assert!(matches(DomainMatch::Subdomains, "github.com", "github.com"));
+    assert!(matches(
+      DomainMatch::Subdomains,
+      ".api.github.com",
+      "github.com"
+    ));
+    assert!(matches(
+      DomainMatch::Subdomains,
+      "API.github.com",
+      ".GitHub.com"
+    ));
+    assert!(!matches(
+      DomainMatch::Subdomains,
+      "github.com",
+      "api.github.com"
+    ));
+    assert!(!matches(
+      DomainMatch::Subdomains,
+      "notgithub.com",
+      "github.com"
+    ));
+    assert!(!matches(
+      DomainMatch::Subdomains,
+      "github.com.evil",
+      "github.com"
+    ));
+  }
+
+  #[test]
+  fn matches_substring() {
+    assert!(matches(
+      DomainMatch::Substring,
+      "notgithub.com.evil",
+      "github.com"
+    ));
+    assert!(matches(DomainMatch::Substring, ".GitHub.com", "github"));
+    assert!(!matches(DomainMatch::Substring, "gitlab.com", "github"));
+  }
+
+  #[test]
+  fn matches_without_filter() {
+    assert!(DomainFilter::new(None, DomainMatch::Exact).matches("github.com"));
+    assert!(DomainFilter::new(Some(vec![]), DomainMatch::Exact).matches("github.com"));
+    assert_eq!(
+      DomainFilter::new(Some(vec![]), DomainMatch::Exact).like_patterns(),
+      None
+    );
+  }
+
+  #[test]
+  fn escapes_like_patterns() {
+    let filter = DomainFilter::new(Some(vec![".A_b%.com".to_string()]), DomainMatch::Subdomains);
+    assert_eq!(filter.like_patterns().unwrap(), ["%a\\_b\\%.com"]);
+    let filter = DomainFilter::new(Some(vec!["a_b.com".to_string()]), DomainMatch::Substring);
+    assert_eq!(filter.like_patterns().unwrap(), ["%a\\_b.com%"]);
+    let filter = DomainFilter::new(Some(vec!["www.a_b.com".to_string()]), DomainMatch::Domain);
+    assert_eq!(
+      filter.like_patterns().unwrap(),
+      [
+        "www.a\\_b.com",
+        ".www.a\\_b.com",
+        "a\\_b.com",
+        ".a\\_b.com",
+        "com",
+        ".com"
+      ]
+    );
+  }
+
+  #[test]
+  fn prefilters_superset_of_matches() {
+    let connection = Connection::open_in_memory().unwrap();
+    connection
+      .execute("CREATE TABLE hosts (host TEXT)", [])
+      .unwrap();
+    for host in HOSTS {
+      connection
+        .execute("INSERT INTO hosts VALUES (?)", [host])
+        .unwrap();
+    }
+    for filter in filters() {
+      let patterns = filter.like_patterns().unwrap();
+      let query = format!(
+        "SELECT host FROM hosts {}",
+        sqlite::like_clause("host", &patterns)
+      );
+      let mut stmt = connection.prepare(&query).unwrap();
+      let mut hosts: Vec<String> = stmt
+        .query_map(params_from_iter(&patterns), |row| row.get(0))
+        .unwrap()
+        .map(|host| host.unwrap())
+        .filter(|host: &String| filter.matches(host))
+        .collect();
+      hosts.sort();
+      assert_eq!(hosts, matching_hosts(&filter), "{:?}", filter);
+    }
+  }
+}
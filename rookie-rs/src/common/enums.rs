@@ -1,6 +1,95 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+/// `rawSameSite` of Firefox cookies without the attribute
+const FIREFOX_SAMESITE_UNSET: i64 = 256;
+
+/// `SameSite` attribute of a cookie, normalized across browsers
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SameSite {
+  /// The site didn't set the attribute, or the browser doesn't store it
+  #[default]
+  Unspecified,
+  None,
+  Lax,
+  Strict,
+}
+
+impl SameSite {
+  /// Maps the `samesite` column of Chromium, -1 unspecified, 0 none, 1 lax, 2 strict
+  pub(crate) fn from_chromium(value: i64) -> Self {
+    match value {
+      0 => SameSite::None,
+      1 => SameSite::Lax,
+      2 => SameSite::Strict,
+      _ => SameSite::Unspecified,
+    }
+  }
+
+  /// Maps the `sameSite` column of Firefox, 0 none, 1 lax, 2 strict
+  ///
+  /// `rawSameSite` keeps the attribute as the site set it, 256 when it didn't set any. When it
+  /// differs from `sameSite` the browser applied its own default.
+  pub(crate) fn from_firefox(value: i64, raw_value: Option<i64>) -> Self {
+    let unset = raw_value.is_some_and(|raw| raw == FIREFOX_SAMESITE_UNSET || raw != value);
+    if unset {
+      return SameSite::Unspecified;
+    }
+    match value {
+      0 => SameSite::None,
+      1 => SameSite::Lax,
+      2 => SameSite::Strict,
+      _ => SameSite::Unspecified,
+    }
+  }
+
+  /// Returns the attribute value as written in `Set-Cookie`, or `Unspecified`
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SameSite::Unspecified => "Unspecified",
+      SameSite::None => "None",
+      SameSite::Lax => "Lax",
+      SameSite::Strict => "Strict",
+    }
+  }
+}
+
+/// Outcome of decrypting a cookie value
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum DecryptionStatus {
+  /// The value was stored in clear or decrypted successfully
+  #[default]
+  Ok,
+  /// The value was decrypted but isn't valid UTF-8, see [`Cookie::value_bytes`]
+  Lossy,
+  /// The value was decrypted but isn't prefixed by the hash of the cookie domain, as it should
+  /// from Chromium cookies DB version 24, the cookie may have been moved from another domain. The
+  /// value is kept as decrypted.
+  DomainHashMismatch,
+  /// The value couldn't be decrypted and is left empty
+  Failed(String),
+}
+
+impl DecryptionStatus {
+  pub fn is_ok(&self) -> bool {
+    *self == DecryptionStatus::Ok
+  }
+}
+
+/// How cookie values which can't be decrypted are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecryptionMode {
+  /// Fail the whole extraction when a value can't be decrypted
+  #[default]
+  Strict,
+  /// Keep every cookie and report failures in [`Cookie::decryption`]
+  Lenient,
+}
+
+/// A browser cookie
+///
+/// `value_bytes` and the fields after `same_site` are optional, they're filled when the browser stores them.
+/// Times are unix timestamps in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Cookie {
   pub domain: String,
   pub path: String,
@@ -8,10 +97,66 @@ pub struct Cookie {
   pub expires: Option<u64>,
   pub name: String,
   pub value: String,
+  /// Bytes of the value when they aren't valid UTF-8, `value` then has invalid sequences replaced
+  /// with `U+FFFD`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub value_bytes: Option<Vec<u8>>,
   pub http_only: bool,
-  pub same_site: i64,
+  pub same_site: SameSite,
+  /// `SameSite` value as stored by the browser, its encoding differs between browsers
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub same_site_raw: Option<i64>,
+  /// Profile directory the cookie was extracted from, when known
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub profile: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub creation: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub last_accessed: Option<u64>,
+  /// Last time the value or attributes changed (Chromium)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub last_update: Option<u64>,
+  /// Eviction priority, 0 low, 1 medium, 2 high (Chromium)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub priority: Option<i64>,
+  /// Scheme of the page which set the cookie, 0 unset, 1 non-secure, 2 secure (Chromium)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source_scheme: Option<i64>,
+  /// Port of the page which set the cookie, -1 unknown (Chromium)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source_port: Option<i64>,
+  /// Schemes the cookie was set from, bits of http 1, https 2, file 4 (Firefox)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub scheme_map: Option<i64>,
+  /// Whether the cookie outlives the browser session
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub is_persistent: Option<bool>,
+  #[serde(default, skip_serializing_if = "DecryptionStatus::is_ok")]
+  pub decryption: DecryptionStatus,
 }
 
+impl Cookie {
+  /// Returns the value as stored by the browser
+  pub fn raw_value(&self) -> &[u8] {
+    self.value_bytes.as_deref().unwrap_or(self.value.as_bytes())
+  }
+}
+
+/// Returns the value as text, along with its bytes when they aren't valid UTF-8
+pub(crate) fn decode_value(bytes: Vec<u8>) -> (String, Option<Vec<u8>>) {
+  match String::from_utf8(bytes) {
+    Ok(value) => (value, None),
+    Err(e) => {
+      let bytes = e.into_bytes();
+      (String::from_utf8_lossy(&bytes).into_owned(), Some(bytes))
+    }
+  }
+}
+
+/// Joins every cookie as `name=value`
+///
+/// To send cookies with a request use [`crate::common::request::cookies_for_url`] instead, which
+/// selects cookies by domain, path and security.
 pub trait CookieToString {
   fn to_string(&self) -> String;
 }
@@ -25,3 +170,20 @@ impl CookieToString for Vec<Cookie> {
       .join(";")
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn maps_firefox_same_site() {
+    assert_eq!(SameSite::from_firefox(0, Some(0)), SameSite::None);
+    assert_eq!(SameSite::from_firefox(1, Some(1)), SameSite::Lax);
+    assert_eq!(SameSite::from_firefox(2, Some(2)), SameSite::Strict);
+    // Lax by default of the browser, the site didn't set the attribute
+    assert_eq!(SameSite::from_firefox(1, Some(256)), SameSite::Unspecified);
+    assert_eq!(SameSite::from_firefox(0, Some(1)), SameSite::Unspecified);
+    // Versions without `rawSameSite`
+    assert_eq!(SameSite::from_firefox(0, None), SameSite::None);
+  }
+}
@@ -1,9 +1,14 @@
 use crate::enums::Cookie;
 
+/// Formats cookies as a JSON array, `same_site` is one of `Unspecified`, `None`, `Lax` or `Strict`
 pub fn json(cookies: Vec<Cookie>) -> String {
   serde_json::to_string_pretty(&cookies).expect("cannot convert cookies to json")
 }
 
+/// Formats cookies as a Netscape cookies file, used by curl and wget
+///
+/// The format has no `SameSite` field, cookies with `SameSite=Strict` may be sent where the browser
+/// wouldn't send them.
 pub fn netscape(cookies: Vec<Cookie>) -> String {
   let mut data = indoc::formatdoc! {"
     # Netscape HTTP Cookie File
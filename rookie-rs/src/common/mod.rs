@@ -1,6 +1,8 @@
+pub mod cancel;
 pub(crate) mod date;
+pub mod domain;
 pub mod enums;
 pub mod format;
 pub(crate) mod paths;
+pub mod request;
 pub(crate) mod sqlite;
-pub(crate) mod utils;
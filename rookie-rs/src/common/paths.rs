@@ -1,6 +1,14 @@
-use crate::{browser::mozilla::get_default_profile, config::Browser};
-use eyre::{anyhow, bail, Context, Result};
-use std::{env, path::PathBuf};
+use crate::{
+  browser::mozilla::{get_default_profile, get_profile_paths},
+  config::Browser,
+};
+#[cfg(not(target_os = "linux"))]
+use eyre::anyhow;
+use eyre::{Context, Result};
+use std::{
+  env,
+  path::{Path, PathBuf},
+};
 
 fn expand_glob_paths(path: PathBuf) -> Result<Vec<PathBuf>> {
   let mut paths: Vec<PathBuf> = vec![];
@@ -14,11 +22,52 @@ fn expand_glob_paths(path: PathBuf) -> Result<Vec<PathBuf>> {
   Ok(paths)
 }
 
-pub fn find_chrome_based_paths(config: &Browser) -> Result<(PathBuf, PathBuf)> {
+/// Returns config channels, keeping only the wanted ones if any given
+///
+/// Channels are compared loosely so that `beta` matches `-beta` and ` Beta`, and `stable` matches the
+/// unnamed default channel.
+pub(crate) fn select_channels(config: &Browser, wanted: &[String]) -> Vec<String> {
+  let channels = config.channels.clone().unwrap_or(vec!["".to_string()]);
+  if wanted.is_empty() {
+    return channels;
+  }
+  channels
+    .into_iter()
+    .filter(|channel| {
+      wanted
+        .iter()
+        .any(|w| normalize_channel(channel) == normalize_channel(w))
+    })
+    .collect()
+}
+
+pub(crate) fn normalize_channel(channel: &str) -> String {
+  let channel = channel.trim_start_matches(['-', ' ', '_']).to_lowercase();
+  if channel.is_empty() {
+    "stable".to_string()
+  } else {
+    channel
+  }
+}
+
+/// Returns the profile directory name of a cookies database path
+pub fn profile_dir_name(db_path: &Path) -> Option<String> {
+  let mut parent = db_path.parent()?;
+  if parent.file_name()? == "Network" {
+    parent = parent.parent()?;
+  }
+  Some(parent.file_name()?.to_string_lossy().to_string())
+}
+
+/// Returns every existing (key, cookies) paths pair of chromium based browser in config order
+pub fn find_all_chrome_based_paths(
+  config: &Browser,
+  channels: &[String],
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+  let mut found: Vec<(PathBuf, PathBuf)> = vec![];
   for path in &config.paths {
     // base paths
-    let channels = config.channels.clone().unwrap_or(vec!["".to_string()]);
-    for channel in channels {
+    for channel in select_channels(config, channels) {
       // channels
       let path = path.replace("{channel}", &channel);
       let db_path = expand_path(path.as_str())?;
@@ -39,20 +88,24 @@ pub fn find_chrome_based_paths(config: &Browser) -> Result<(PathBuf, PathBuf)> {
               db_path.display(),
               key_path.display()
             );
-            return Ok((key_path, db_path));
+            if !found.iter().any(|(_, p)| p == &db_path) {
+              found.push((key_path, db_path));
+            }
           }
         }
       }
     }
   }
-  Err(anyhow!("can't find cookies file"))
+  Ok(found)
 }
 
-pub fn find_mozilla_based_paths(config: &Browser) -> Result<PathBuf> {
+/// Returns every existing cookies path of mozilla based browser, default profiles first
+pub fn find_all_mozilla_based_paths(config: &Browser, channels: &[String]) -> Result<Vec<PathBuf>> {
+  let mut defaults: Vec<PathBuf> = vec![];
+  let mut others: Vec<PathBuf> = vec![];
   for path in &config.paths {
     // base paths
-    let channels = config.channels.clone().unwrap_or(vec!["".to_string()]);
-    for channel in channels {
+    for channel in select_channels(config, channels) {
       // channels
       let path = path.replace("{channel}", &channel);
       let firefox_path = expand_path(path.as_str())?;
@@ -62,16 +115,23 @@ pub fn find_mozilla_based_paths(config: &Browser) -> Result<PathBuf> {
         let profiles_path = path.join("profiles.ini");
         let default_profile =
           get_default_profile(profiles_path.as_path()).unwrap_or("".to_string());
-        let db_path = path.join(default_profile).join("cookies.sqlite");
-        if db_path.exists() {
+        let db_path = path.join(&default_profile).join("cookies.sqlite");
+        if db_path.exists() && !defaults.contains(&db_path) {
           log::debug!("Found mozilla path {}", db_path.display());
-          return Ok(db_path);
+          defaults.push(db_path);
+        }
+        for profile in get_profile_paths(profiles_path.as_path()).unwrap_or_default() {
+          let db_path = path.join(profile).join("cookies.sqlite");
+          if db_path.exists() && !defaults.contains(&db_path) && !others.contains(&db_path) {
+            log::debug!("Found mozilla path {}", db_path.display());
+            others.push(db_path);
+          }
         }
       }
     }
   }
-
-  bail!("Can't find cookies file")
+  defaults.extend(others);
+  Ok(defaults)
 }
 
 #[cfg(target_os = "macos")]
@@ -93,7 +153,7 @@ pub fn find_safari_based_paths(config: &Browser) -> Result<PathBuf> {
       }
     }
   }
-  bail!("Can't find cookies file")
+  Err(anyhow!("Can't find cookies file"))
 }
 
 #[cfg(target_os = "windows")]
@@ -117,7 +177,7 @@ pub fn find_ie_based_paths(config: &Browser) -> Result<PathBuf> {
     }
   }
 
-  bail!("Can't find cookies file")
+  Err(anyhow!("Can't find cookies file"))
 }
 #[cfg(target_os = "windows")]
 pub fn expand_path(path: &str) -> Result<PathBuf> {
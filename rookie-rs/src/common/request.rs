@@ -0,0 +1,349 @@
+use crate::{
+  common::{date, domain::DomainMatch},
+  enums::{Cookie, SameSite},
+  Result,
+};
+use percent_encoding::percent_decode_str;
+use std::cmp::Reverse;
+use url::Url;
+
+/// How a request is made, which decides whether `SameSite` cookies are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestContext {
+  /// The request is initiated by the same site as its URL
+  pub same_site: bool,
+  /// The request navigates the top level window (following a link, typing the URL)
+  pub top_level_navigation: bool,
+  /// The request method is safe (`GET`, `HEAD`, `OPTIONS`, `TRACE`)
+  pub safe_method: bool,
+  /// Cookies without `SameSite` attribute are treated as `Lax`, as Chromium does. Firefox and
+  /// Safari send them like `SameSite=None`.
+  pub unspecified_as_lax: bool,
+}
+
+impl Default for RequestContext {
+  /// Same site `GET` navigation, as when the user opens the URL
+  fn default() -> Self {
+    Self {
+      same_site: true,
+      top_level_navigation: true,
+      safe_method: true,
+      unspecified_as_lax: true,
+    }
+  }
+}
+
+impl RequestContext {
+  /// Cross site `GET` navigation, as when following a link from another site
+  pub fn cross_site_navigation() -> Self {
+    Self {
+      same_site: false,
+      ..Self::default()
+    }
+  }
+
+  /// Cross site subresource request (image, iframe, fetch)
+  pub fn cross_site_subresource() -> Self {
+    Self {
+      same_site: false,
+      top_level_navigation: false,
+      ..Self::default()
+    }
+  }
+
+  /// Returns true if a cookie with the `SameSite` attribute is sent
+  fn allows(&self, same_site: SameSite) -> bool {
+    if self.same_site {
+      return true;
+    }
+    match same_site {
+      SameSite::None => true,
+      SameSite::Strict => false,
+      SameSite::Unspecified if !self.unspecified_as_lax => true,
+      SameSite::Lax | SameSite::Unspecified => self.top_level_navigation && self.safe_method,
+    }
+  }
+}
+
+/// Returns the cookies a browser would send to the URL, in the order it would send them
+///
+/// Cookies are selected by RFC 6265 domain-match and path-match, `Secure` cookies are only sent
+/// over `https` / `wss`, expired cookies are skipped and `SameSite` is checked against the context.
+/// Paths are compared percent-decoded. Longer paths come first, then earlier creation times,
+/// cookies without creation time come last.
+/// When the same cookie (domain, path and name) is found more than once, the first one is kept.
+///
+/// # Examples
+///
+/// ```
+/// use rookie::{common::request::{cookie_header, cookies_for_url, RequestContext}, enums::Cookie};
+///
+/// let cookie = |domain: &str, path: &str, name: &str| Cookie {
+///   domain: domain.to_string(),
+///   path: path.to_string(),
+///   secure: false,
+///   expires: None,
+///   name: name.to_string(),
+///   value: "1".to_string(),
+///   http_only: false,
+///   ..Default::default()
+/// };
+/// let cookies = vec![
+///   cookie(".github.com", "/", "a"),
+///   cookie("github.com", "/login", "b"),
+///   cookie(".notgithub.com", "/", "c"),
+/// ];
+/// let url = "https://github.com/login/oauth";
+/// let selected = cookies_for_url(&cookies, url, RequestContext::default()).unwrap();
+/// assert_eq!(cookie_header(&selected), "b=1; a=1");
+/// ```
+pub fn cookies_for_url<'a>(
+  cookies: &'a [Cookie],
+  url: &str,
+  context: RequestContext,
+) -> Result<Vec<&'a Cookie>> {
+  let url =
+    Url::parse(url).map_err(|e| eyre::Report::new(e).wrap_err(format!("Invalid URL {}", url)))?;
+  let host = url.host_str().unwrap_or_default();
+  // Paths are compared decoded, browsers may store cookie paths either way
+  let path = percent_decode_str(url.path()).decode_utf8_lossy();
+  let secure = matches!(url.scheme(), "https" | "wss");
+  let now = date::unix_now();
+
+  let mut selected: Vec<&Cookie> = vec![];
+  for cookie in cookies {
+    let sent = DomainMatch::Domain.matches(&cookie.domain, host)
+      && path_matches(&path, &percent_decode_str(&cookie.path).decode_utf8_lossy())
+      && (secure || !cookie.secure)
+      && cookie.expires.map_or(true, |expires| expires > now)
+      && context.allows(cookie.same_site);
+    let duplicate = selected.iter().any(|other| {
+      other.domain == cookie.domain && other.path == cookie.path && other.name == cookie.name
+    });
+    if sent && !duplicate {
+      selected.push(cookie);
+    }
+  }
+  // Stable sort keeps the order of cookies without creation time
+  selected.sort_by_key(|cookie| {
+    (
+      Reverse(cookie.path.len()),
+      cookie.creation.unwrap_or(u64::MAX),
+    )
+  });
+  Ok(selected)
+}
+
+/// Returns the `Cookie` header value of cookies, such as `a=1; b=2`
+pub fn cookie_header(cookies: &[&Cookie]) -> String {
+  cookies
+    .iter()
+    .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+    .collect::<Vec<String>>()
+    .join("; ")
+}
+
+/// Returns true if the request path path-matches the cookie path, as defined by RFC 6265
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+  // Cookies without path default to the root
+  let cookie_path = if cookie_path.is_empty() {
+    "/"
+  } else {
+    cookie_path
+  };
+  match request_path.strip_prefix(cookie_path) {
+    Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+    None => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cookie(domain: &str, path: &str, name: &str) -> Cookie {
+    Cookie {
+      domain: domain.to_string(),
+      path: path.to_string(),
+      name: name.to_string(),
+      value: "1".to_string(),
+      ..Default::default()
+    }
+  }
+
+  fn names(cookies: &[Cookie], url: &str, context: RequestContext) -> Vec<String> {
+    cookies_for_url(cookies, url, context)
+      .unwrap()
+      .into_iter()
+      .map(|cookie| cookie.name.clone())
+      .collect()
+  }
+
+  #[test]
+  fn matches_paths() {
+    assert!(path_matches("/a", "/a"));
+    assert!(path_matches("/a/b", "/a"));
+    assert!(path_matches("/a/", "/a"));
+    assert!(path_matches("/a/b", "/a/"));
+    assert!(path_matches("/anything", ""));
+    assert!(!path_matches("/ab", "/a"));
+    assert!(!path_matches("/a", "/a/"));
+    assert!(!path_matches("/b", "/a"));
+  }
+
+  #[test]
+  fn decodes_paths() {
+    let cookies = vec![
+      cookie("example.com", "/a b", "decoded"),
+      cookie("example.com", "/a%20b", "encoded"),
+      cookie("example.com", "/é", "unicode"),
+    ];
+    let context = RequestContext::default();
+    let mut selected = names(&cookies, "https://example.com/a b/c", context);
+    selected.sort();
+    assert_eq!(selected, ["decoded", "encoded"]);
+    assert_eq!(
+      names(&cookies, "https://example.com/é", context),
+      ["unicode"]
+    );
+  }
+
+  #[test]
+  fn matches_host_only_and_domain_cookies() {
+    let cookies = vec![
+      cookie("example.com", "/", "host"),
+      cookie(".example.com", "/", "domain"),
+    ];
+    let context = RequestContext::default();
+    assert_eq!(
+      names(&cookies, "https://example.com/", context),
+      ["host", "domain"]
+    );
+    assert_eq!(
+      names(&cookies, "https://www.example.com/", context),
+      ["domain"]
+    );
+    assert!(names(&cookies, "https://notexample.com/", context).is_empty());
+  }
+
+  #[test]
+  fn sends_secure_cookies_over_https() {
+    let cookies = vec![
+      Cookie {
+        secure: true,
+        ..cookie("example.com", "/", "secure")
+      },
+      cookie("example.com", "/", "plain"),
+    ];
+    let context = RequestContext::default();
+    assert_eq!(
+      names(&cookies, "https://example.com/", context),
+      ["secure", "plain"]
+    );
+    assert_eq!(
+      names(&cookies, "wss://example.com/", context),
+      ["secure", "plain"]
+    );
+    assert_eq!(names(&cookies, "http://example.com/", context), ["plain"]);
+  }
+
+  #[test]
+  fn skips_expired_cookies() {
+    let now = date::unix_now();
+    let cookies = vec![
+      Cookie {
+        expires: Some(now - 60),
+        ..cookie("example.com", "/", "expired")
+      },
+      Cookie {
+        expires: Some(now + 60),
+        ..cookie("example.com", "/", "valid")
+      },
+      cookie("example.com", "/", "session"),
+    ];
+    let selected = names(&cookies, "https://example.com/", RequestContext::default());
+    assert_eq!(selected, ["valid", "session"]);
+  }
+
+  #[test]
+  fn checks_same_site() {
+    let same_site = |name: &str, same_site| Cookie {
+      same_site,
+      ..cookie("example.com", "/", name)
+    };
+    let cookies = vec![
+      same_site("strict", SameSite::Strict),
+      same_site("lax", SameSite::Lax),
+      same_site("none", SameSite::None),
+      same_site("unspecified", SameSite::Unspecified),
+    ];
+    let url = "https://example.com/";
+    assert_eq!(
+      names(&cookies, url, RequestContext::default()),
+      ["strict", "lax", "none", "unspecified"]
+    );
+    assert_eq!(
+      names(&cookies, url, RequestContext::cross_site_navigation()),
+      ["lax", "none", "unspecified"]
+    );
+    let cross_site_post = RequestContext {
+      safe_method: false,
+      ..RequestContext::cross_site_navigation()
+    };
+    assert_eq!(names(&cookies, url, cross_site_post), ["none"]);
+    assert_eq!(
+      names(&cookies, url, RequestContext::cross_site_subresource()),
+      ["none"]
+    );
+    let unspecified_as_none = RequestContext {
+      unspecified_as_lax: false,
+      ..RequestContext::cross_site_subresource()
+    };
+    assert_eq!(
+      names(&cookies, url, unspecified_as_none),
+      ["none", "unspecified"]
+    );
+  }
+
+  #[test]
+  fn keeps_first_duplicate() {
+    let cookies = vec![
+      Cookie {
+        value: "first".to_string(),
+        ..cookie("example.com", "/", "a")
+      },
+      Cookie {
+        value: "second".to_string(),
+        ..cookie("example.com", "/", "a")
+      },
+      cookie(".example.com", "/", "a"),
+      cookie("example.com", "/path", "a"),
+    ];
+    let selected =
+      cookies_for_url(&cookies, "https://example.com/path", Default::default()).unwrap();
+    assert_eq!(cookie_header(&selected), "a=1; a=first; a=1");
+  }
+
+  #[test]
+  fn orders_by_path_then_creation() {
+    let created = |name: &str, path: &str, creation| Cookie {
+      creation,
+      ..cookie("example.com", path, name)
+    };
+    let cookies = vec![
+      created("unknown", "/", None),
+      created("late", "/", Some(2)),
+      created("early", "/", Some(1)),
+      created("long", "/a/b", Some(3)),
+      created("medium", "/a", Some(3)),
+    ];
+    assert_eq!(
+      names(
+        &cookies,
+        "https://example.com/a/b",
+        RequestContext::default()
+      ),
+      ["long", "medium", "early", "late", "unknown"]
+    );
+  }
+}
@@ -1,14 +1,83 @@
+use crate::RookieError;
 use eyre::{anyhow, Result};
-use rusqlite::{Connection, OpenFlags};
-use std::path::PathBuf;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use std::{
+  io::ErrorKind,
+  path::{Path, PathBuf},
+};
 use url::Url;
 
 pub fn connect(path: PathBuf) -> Result<Connection> {
   let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI;
+  let canonical_path = path.canonicalize().map_err(|e| match e.kind() {
+    ErrorKind::PermissionDenied => RookieError::PermissionDenied {
+      path: path.clone(),
+      hint: None,
+      source: e,
+    }
+    .into(),
+    _ => anyhow!(e),
+  })?;
   let conn_str = format!(
     "{}?mode=ro&immutable=1",
-    Url::from_file_path(path.canonicalize()?).or(Err(anyhow!("Error opening connection")))?
+    Url::from_file_path(canonical_path).or(Err(anyhow!("Error opening connection")))?
   );
-  let connection = rusqlite::Connection::open_with_flags(conn_str, flags)?;
+  let connection = rusqlite::Connection::open_with_flags(conn_str, flags)
+    .map_err(|e| map_error(path.as_path(), e))?;
   Ok(connection)
 }
+
+/// Returns the selectable expression of a column, or `NULL` if the table doesn't have it
+///
+/// Columns added by newer browser versions are missing from older databases.
+pub fn optional_column(connection: &Connection, table: &str, column: &str) -> Result<String> {
+  let mut stmt = connection.prepare(&format!("PRAGMA table_info({})", table))?;
+  let mut rows = stmt.query([])?;
+  while let Some(row) = rows.next()? {
+    let name: String = row.get(1)?;
+    if name == column {
+      return Ok(column.to_string());
+    }
+  }
+  Ok("NULL".to_string())
+}
+
+/// Returns a `WHERE` clause matching the column against `LIKE` patterns bound as parameters
+///
+/// Patterns use `\` as escape character, see [`escape_like`].
+pub fn like_clause(column: &str, patterns: &[String]) -> String {
+  let conditions: Vec<String> = patterns
+    .iter()
+    .map(|_| format!("{} LIKE ? ESCAPE '\\'", column))
+    .collect();
+  format!("WHERE ({}) ", conditions.join(" OR "))
+}
+
+/// Escapes `LIKE` wildcards so that the text is matched literally
+pub fn escape_like(text: &str) -> String {
+  text
+    .replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+/// Converts SQLite errors to typed errors where possible
+pub fn map_error(path: &Path, error: rusqlite::Error) -> eyre::Report {
+  let code = error.sqlite_error_code();
+  let message = error.to_string();
+  if matches!(
+    code,
+    Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked)
+  ) || (cfg!(windows) && code == Some(ErrorCode::CannotOpen) && path.exists())
+  {
+    return RookieError::DatabaseLocked {
+      path: path.to_path_buf(),
+      source: Box::new(error),
+    }
+    .into();
+  }
+  if message.starts_with("no such table") || message.starts_with("no such column") {
+    return RookieError::UnsupportedSchema(format!("{} in {}", message, path.display())).into();
+  }
+  anyhow!(error)
+}
@@ -22,21 +22,22 @@ pub struct Config {
 pub static CONFIG: Lazy<Config> =
   Lazy::new(|| serde_json::from_str(include_str!("../config.json")).unwrap());
 
-#[allow(clippy::unnecessary_to_owned)]
-pub fn get_browser_config(name: &str) -> &Browser {
-  let platform = if cfg!(windows) {
+/// Returns the name of the current platform as used in config.json
+pub fn current_platform() -> &'static str {
+  if cfg!(windows) {
     "windows"
   } else if cfg!(target_os = "macos") {
     "macos"
   } else {
     "linux"
-  };
-  let config = CONFIG
-    .platforms
-    .get(platform)
-    .unwrap()
-    .get(name)
-    .unwrap()
-    .to_owned();
-  config
+  }
+}
+
+/// Returns browser config for the current platform, if the browser is supported on it
+pub fn find_browser_config(name: &str) -> Option<&'static Browser> {
+  CONFIG.platforms.get(current_platform())?.get(name)
+}
+
+pub fn get_browser_config(name: &str) -> &'static Browser {
+  find_browser_config(name).unwrap()
 }
@@ -0,0 +1,78 @@
+use std::{io, path::PathBuf, time::Duration};
+use thiserror::Error;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors returned by rookie
+///
+/// Internal failures which don't fit any variant are kept in [`RookieError::Other`],
+/// the original error is available through [`std::error::Error::source`].
+#[derive(Debug, Error)]
+pub enum RookieError {
+  /// No cookies file of the browser was found
+  #[error("{0} is not installed or has no cookies file")]
+  BrowserNotInstalled(String),
+
+  /// The browser isn't available on the current platform
+  #[error("{0} is not supported on this platform")]
+  UnsupportedBrowser(String),
+
+  /// The keyring / keychain holding the decryption key is locked or refused access
+  #[error("Keyring is locked: {0}")]
+  KeyringLocked(String),
+
+  /// The cookies database is locked by another process
+  #[error("Database {} is locked", path.display())]
+  DatabaseLocked {
+    path: PathBuf,
+    #[source]
+    source: BoxError,
+  },
+
+  /// Cookies can't be decrypted with any of the available keys
+  #[error("Decryption failed: {0}")]
+  DecryptionFailed(String),
+
+  /// The cookies file layout isn't supported
+  #[error("Unsupported schema: {0}")]
+  UnsupportedSchema(String),
+
+  /// The current process isn't allowed to read a file
+  #[error(
+    "Permission denied for {}{}",
+    path.display(),
+    hint.as_ref().map(|hint| format!("\n{}", hint)).unwrap_or_default()
+  )]
+  PermissionDenied {
+    path: PathBuf,
+    hint: Option<String>,
+    #[source]
+    source: io::Error,
+  },
+
+  /// An operation which may block didn't finish in time
+  #[error("{operation} timed out after {}s", timeout.as_secs_f32())]
+  Timeout {
+    operation: String,
+    timeout: Duration,
+  },
+
+  /// An operation was cancelled through its [`CancelHandle`](crate::common::cancel::CancelHandle)
+  #[error("{0} was cancelled")]
+  Cancelled(String),
+
+  #[error(transparent)]
+  Other(eyre::Report),
+}
+
+impl From<eyre::Report> for RookieError {
+  /// Recovers typed errors which were raised through eyre
+  fn from(report: eyre::Report) -> Self {
+    match report.downcast::<RookieError>() {
+      Ok(error) => error,
+      Err(report) => RookieError::Other(report),
+    }
+  }
+}
+
+pub type Result<T, E = RookieError> = std::result::Result<T, E>;
@@ -0,0 +1,536 @@
+use super::reader::Reader;
+use crate::RookieError;
+use eyre::{bail, Result};
+use hmac::{Hmac, Mac};
+use md4::Md4;
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha384, Sha512};
+use std::{fmt, path::PathBuf};
+
+const CALG_SHA1: u32 = 0x8004;
+const CALG_HMAC: u32 = 0x8009;
+const CALG_SHA_256: u32 = 0x800c;
+const CALG_SHA_384: u32 = 0x800d;
+const CALG_SHA_512: u32 = 0x800e;
+const CALG_AES_128: u32 = 0x660e;
+const CALG_AES_192: u32 = 0x660f;
+const CALG_AES_256: u32 = 0x6610;
+
+/// Size of the header of master key files, before the master key itself
+const MASTER_KEY_FILE_HEADER: usize = 128;
+const AES_BLOCK_SIZE: usize = 16;
+
+/// Secret of a Windows user, which protects their DPAPI master keys
+#[derive(Clone)]
+pub enum DpapiSecret {
+  /// Login password of the user
+  Password(String),
+  /// NT hash of the password (MD4 of its UTF-16), which only unlocks the master keys of domain
+  /// users, local accounts protect them with the SHA-1 of the password
+  NtHash([u8; 16]),
+}
+
+impl fmt::Debug for DpapiSecret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DpapiSecret::Password(_) => f.write_str("Password(..)"),
+      DpapiSecret::NtHash(_) => f.write_str("NtHash(..)"),
+    }
+  }
+}
+
+/// DPAPI master keys of a Windows user, the `%APPDATA%\Microsoft\Protect\<SID>` directory
+///
+/// Decrypts DPAPI blobs without Windows, such as the key of a `Local State` file copied from
+/// another machine. Master keys of Windows 7 and later (AES and SHA-2) are supported.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::keys::{DpapiMasterKeys, DpapiSecret};
+///
+/// let dir = "/image/Users/alice/AppData/Roaming/Microsoft/Protect/S-1-5-21-1-2-3-1001";
+/// let master_keys = DpapiMasterKeys::new(dir, DpapiSecret::Password("hunter2".to_string()));
+/// let blob = std::fs::read("/image/blob.bin").unwrap();
+/// let plaintext = master_keys.unprotect(&blob).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DpapiMasterKeys {
+  dir: PathBuf,
+  sid: String,
+  secret: DpapiSecret,
+}
+
+impl DpapiMasterKeys {
+  /// Master keys of a `Protect/<SID>` directory, the SID being the name of the directory
+  pub fn new(dir: impl Into<PathBuf>, secret: DpapiSecret) -> Self {
+    let dir = dir.into();
+    let sid = dir
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    Self { dir, sid, secret }
+  }
+
+  /// Sets the SID of the user, when the directory was renamed
+  pub fn sid(mut self, sid: impl Into<String>) -> Self {
+    self.sid = sid.into();
+    self
+  }
+
+  /// Returns the data of a DPAPI blob, as `CryptUnprotectData` without entropy would
+  pub fn unprotect(&self, blob: &[u8]) -> crate::Result<Vec<u8>> {
+    let blob = Blob::parse(blob)?;
+    let master_key = self.master_key(&blob.master_key)?;
+    Ok(blob.decrypt(&master_key)?)
+  }
+
+  /// Returns the master key of the file named after its GUID
+  pub fn master_key(&self, guid: &str) -> crate::Result<Vec<u8>> {
+    let path = self.dir.join(guid.to_lowercase());
+    let data = std::fs::read(&path)
+      .map_err(|e| eyre::Report::new(e).wrap_err(format!("Can't read {}", path.display())))?;
+    let master_key = MasterKey::parse(&data)?;
+    for pre_key in self.pre_keys() {
+      if let Some(key) = master_key.decrypt(&pre_key) {
+        return Ok(key);
+      }
+    }
+    Err(RookieError::DecryptionFailed(format!(
+      "Wrong password or SID {} of DPAPI master key {}",
+      self.sid, guid
+    )))
+  }
+
+  /// Returns the keys which may protect the master keys, derived from the secret and the SID
+  fn pre_keys(&self) -> Vec<Vec<u8>> {
+    let sid = utf16(&self.sid);
+    let sid_nul = utf16(&format!("{}\0", self.sid));
+    let (password_hash, nt_hash) = match &self.secret {
+      DpapiSecret::Password(password) => {
+        let password = utf16(password);
+        (
+          Some(Sha1::digest(&password).to_vec()),
+          Md4::digest(&password).to_vec(),
+        )
+      }
+      DpapiSecret::NtHash(nt_hash) => (None, nt_hash.to_vec()),
+    };
+
+    let mut pre_keys = vec![];
+    // Local accounts
+    if let Some(password_hash) = password_hash {
+      pre_keys.push(HashAlgorithm::Sha1.hmac(&password_hash, &[&sid_nul]));
+    }
+    // Domain accounts
+    pre_keys.push(HashAlgorithm::Sha1.hmac(&nt_hash, &[&sid_nul]));
+    // Domain accounts in the Protected Users group
+    let mut derived = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(&nt_hash, &sid, 10000, &mut derived);
+    let mut protected = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(&derived, &sid, 1, &mut protected);
+    pre_keys.push(HashAlgorithm::Sha1.hmac(&protected[..16], &[&sid_nul]));
+    pre_keys
+  }
+}
+
+/// Master key encrypted with a key derived from the secret of the user
+struct MasterKey<'a> {
+  salt: &'a [u8],
+  rounds: u32,
+  hash: HashAlgorithm,
+  key_len: usize,
+  data: &'a [u8],
+}
+
+impl<'a> MasterKey<'a> {
+  fn parse(data: &'a [u8]) -> Result<Self> {
+    let mut reader = Reader::new(data);
+    reader.bytes(MASTER_KEY_FILE_HEADER - 32)?; // version, GUID and policy
+    let master_key_len = reader.u64_le()? as usize;
+    reader.bytes(24)?; // sizes of the backup key, credential history and domain key
+
+    let mut reader = Reader::new(reader.bytes(master_key_len)?);
+    reader.u32_le()?; // version
+    let salt = reader.bytes(16)?;
+    let rounds = reader.u32_le()?;
+    let hash = HashAlgorithm::from_alg_id(reader.u32_le()?)?;
+    let key_len = aes_key_len(reader.u32_le()?)?;
+    Ok(Self {
+      salt,
+      rounds,
+      hash,
+      key_len,
+      data: reader.rest(),
+    })
+  }
+
+  /// Returns the master key, or nothing if the pre-key is wrong
+  fn decrypt(&self, pre_key: &[u8]) -> Option<Vec<u8>> {
+    // DPAPI's PBKDF2 feeds each round with the XOR of the previous ones, other implementations
+    // follow the RFC, the HMAC of the master key tells which one is right
+    for dpapi_rounds in [true, false] {
+      let derived = pbkdf2(
+        self.hash,
+        pre_key,
+        self.salt,
+        self.rounds,
+        self.key_len + AES_BLOCK_SIZE,
+        dpapi_rounds,
+      );
+      let (key, iv) = derived.split_at(self.key_len);
+      let Ok(plaintext) = aes_cbc_decrypt(key, iv, self.data, false) else {
+        continue;
+      };
+      // HMAC salt, HMAC and master key
+      let hmac_len = self.hash.output_size();
+      if plaintext.len() <= AES_BLOCK_SIZE + hmac_len {
+        continue;
+      }
+      let (salt, rest) = plaintext.split_at(AES_BLOCK_SIZE);
+      let (hmac, master_key) = rest.split_at(hmac_len);
+      let hmac_key = self.hash.hmac(pre_key, &[salt]);
+      if self.hash.hmac(&hmac_key, &[master_key]) == hmac {
+        return Some(master_key.to_vec());
+      }
+    }
+    None
+  }
+}
+
+/// Data encrypted by `CryptProtectData`
+struct Blob<'a> {
+  master_key: String,
+  key_len: usize,
+  salt: &'a [u8],
+  hash: HashAlgorithm,
+  hmac: &'a [u8],
+  data: &'a [u8],
+  /// Signed part of the blob, from the master key version to the data
+  signed: &'a [u8],
+  sign: &'a [u8],
+}
+
+impl<'a> Blob<'a> {
+  fn parse(blob: &'a [u8]) -> Result<Self> {
+    let mut reader = Reader::new(blob);
+    reader.u32_le()?; // version
+    reader.bytes(16)?; // provider
+    let signed_start = reader.offset();
+    reader.u32_le()?; // master key version
+    let master_key = guid(reader.bytes(16)?);
+    reader.u32_le()?; // flags
+    reader.sized_le()?; // description
+    let key_len = aes_key_len(reader.u32_le()?)?;
+    reader.u32_le()?; // key size in bits
+    let salt = reader.sized_le()?;
+    reader.sized_le()?; // strong password salt
+    let hash = HashAlgorithm::from_alg_id(reader.u32_le()?)?;
+    reader.u32_le()?; // hash size in bits
+    let hmac = reader.sized_le()?;
+    let data = reader.sized_le()?;
+    let signed = &blob[signed_start..reader.offset()];
+    let sign = reader.sized_le()?;
+    Ok(Self {
+      master_key,
+      key_len,
+      salt,
+      hash,
+      hmac,
+      data,
+      signed,
+      sign,
+    })
+  }
+
+  fn decrypt(&self, master_key: &[u8]) -> Result<Vec<u8>> {
+    let key_hash = Sha1::digest(master_key);
+    if !self.is_signed_by(&key_hash) {
+      return Err(
+        RookieError::DecryptionFailed(format!(
+          "DPAPI blob isn't protected by master key {}",
+          self.master_key
+        ))
+        .into(),
+      );
+    }
+    let session_key = self.hash.hmac(&key_hash, &[self.salt]);
+    let key = derive_key(self.hash, session_key, self.key_len);
+    aes_cbc_decrypt(&key, &[0u8; AES_BLOCK_SIZE], self.data, true)
+  }
+
+  /// Returns true if the blob was signed with the master key, Windows XP signs with the hash of
+  /// the inner HMAC instead of the HMAC itself
+  fn is_signed_by(&self, key_hash: &[u8]) -> bool {
+    if self.hash.hmac(key_hash, &[self.hmac, self.signed]) == self.sign {
+      return true;
+    }
+    let mut key = key_hash.to_vec();
+    key.resize(self.hash.block_size(), 0);
+    let ipad: Vec<u8> = key.iter().map(|byte| byte ^ 0x36).collect();
+    let opad: Vec<u8> = key.iter().map(|byte| byte ^ 0x5c).collect();
+    let inner = self.hash.digest(&[&ipad, self.hmac].concat());
+    self.hash.digest(&[&opad, &inner, self.signed].concat()) == self.sign
+  }
+}
+
+/// Hash algorithms of DPAPI, SHA-1 being the one of `CALG_HMAC`
+#[derive(Debug, Clone, Copy)]
+enum HashAlgorithm {
+  Sha1,
+  Sha256,
+  Sha384,
+  Sha512,
+}
+
+impl HashAlgorithm {
+  fn from_alg_id(alg_id: u32) -> Result<Self> {
+    Ok(match alg_id {
+      CALG_SHA1 | CALG_HMAC => HashAlgorithm::Sha1,
+      CALG_SHA_256 => HashAlgorithm::Sha256,
+      CALG_SHA_384 => HashAlgorithm::Sha384,
+      CALG_SHA_512 => HashAlgorithm::Sha512,
+      _ => return Err(RookieError::UnsupportedSchema(format!("DPAPI hash {:#x}", alg_id)).into()),
+    })
+  }
+
+  fn block_size(self) -> usize {
+    match self {
+      HashAlgorithm::Sha1 | HashAlgorithm::Sha256 => 64,
+      HashAlgorithm::Sha384 | HashAlgorithm::Sha512 => 128,
+    }
+  }
+
+  fn output_size(self) -> usize {
+    match self {
+      HashAlgorithm::Sha1 => 20,
+      HashAlgorithm::Sha256 => 32,
+      HashAlgorithm::Sha384 => 48,
+      HashAlgorithm::Sha512 => 64,
+    }
+  }
+
+  fn digest(self, data: &[u8]) -> Vec<u8> {
+    match self {
+      HashAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+      HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+      HashAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+      HashAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+    }
+  }
+
+  /// Returns the HMAC of the concatenated parts
+  fn hmac(self, key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    match self {
+      HashAlgorithm::Sha1 => mac::<Hmac<Sha1>>(key, parts),
+      HashAlgorithm::Sha256 => mac::<Hmac<Sha256>>(key, parts),
+      HashAlgorithm::Sha384 => mac::<Hmac<Sha384>>(key, parts),
+      HashAlgorithm::Sha512 => mac::<Hmac<Sha512>>(key, parts),
+    }
+  }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+  let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any size");
+  for part in parts {
+    mac.update(part);
+  }
+  mac.finalize().into_bytes().to_vec()
+}
+
+/// PBKDF2 of master keys, `dpapi_rounds` feeding each round with the XOR of the previous ones
+fn pbkdf2(
+  hash: HashAlgorithm,
+  password: &[u8],
+  salt: &[u8],
+  rounds: u32,
+  len: usize,
+  dpapi_rounds: bool,
+) -> Vec<u8> {
+  let mut output = vec![];
+  for block in 1u32.. {
+    if output.len() >= len {
+      break;
+    }
+    let mut u = hash.hmac(password, &[salt, &block.to_be_bytes()]);
+    let mut xored = u.clone();
+    for _ in 1..rounds {
+      u = hash.hmac(password, &[&u]);
+      xored.iter_mut().zip(&u).for_each(|(x, u)| *x ^= u);
+      if dpapi_rounds {
+        u.clone_from(&xored);
+      }
+    }
+    output.extend(xored);
+  }
+  output.truncate(len);
+  output
+}
+
+/// Derives the cipher key from the session key, as `CryptDeriveKey` does
+fn derive_key(hash: HashAlgorithm, session_key: Vec<u8>, key_len: usize) -> Vec<u8> {
+  let mut session_key = match session_key.len() > hash.block_size() {
+    true => hash.digest(&session_key),
+    false => session_key,
+  };
+  if session_key.len() >= key_len {
+    session_key.truncate(key_len);
+    return session_key;
+  }
+  session_key.resize(hash.block_size(), 0);
+  let ipad: Vec<u8> = session_key.iter().map(|byte| byte ^ 0x36).collect();
+  let opad: Vec<u8> = session_key.iter().map(|byte| byte ^ 0x5c).collect();
+  let mut key = hash.digest(&ipad);
+  key.extend(hash.digest(&opad));
+  key.truncate(key_len);
+  key
+}
+
+fn aes_key_len(alg_id: u32) -> Result<usize> {
+  Ok(match alg_id {
+    CALG_AES_128 => 16,
+    CALG_AES_192 => 24,
+    CALG_AES_256 => 32,
+    _ => return Err(RookieError::UnsupportedSchema(format!("DPAPI cipher {:#x}", alg_id)).into()),
+  })
+}
+
+fn aes_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8], padded: bool) -> Result<Vec<u8>> {
+  use aes::cipher::{
+    block_padding::{NoPadding, Pkcs7},
+    BlockDecryptMut, KeyIvInit,
+  };
+
+  fn decrypt<C: BlockDecryptMut + KeyIvInit>(
+    key: &[u8],
+    iv: &[u8],
+    data: &[u8],
+    padded: bool,
+  ) -> Result<Vec<u8>> {
+    let cipher = C::new_from_slices(key, iv).map_err(|_| eyre::eyre!("Invalid AES key size"))?;
+    let mut data = data.to_vec();
+    let len = match padded {
+      true => cipher
+        .decrypt_padded_mut::<Pkcs7>(&mut data)
+        .map(<[u8]>::len),
+      false => cipher
+        .decrypt_padded_mut::<NoPadding>(&mut data)
+        .map(<[u8]>::len),
+    }
+    .map_err(|_| eyre::eyre!("Can't decrypt DPAPI data"))?;
+    data.truncate(len);
+    Ok(data)
+  }
+
+  match key.len() {
+    16 => decrypt::<cbc::Decryptor<aes::Aes128>>(key, iv, data, padded),
+    24 => decrypt::<cbc::Decryptor<aes::Aes192>>(key, iv, data, padded),
+    32 => decrypt::<cbc::Decryptor<aes::Aes256>>(key, iv, data, padded),
+    len => bail!("Invalid AES key size: {}", len),
+  }
+}
+
+/// Formats a GUID as the name of its master key file
+fn guid(bytes: &[u8]) -> String {
+  let hex = |bytes: &[u8]| {
+    bytes
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect::<String>()
+  };
+  format!(
+    "{:08x}-{:04x}-{:04x}-{}-{}",
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    u16::from_le_bytes([bytes[4], bytes[5]]),
+    u16::from_le_bytes([bytes[6], bytes[7]]),
+    hex(&bytes[8..10]),
+    hex(&bytes[10..16])
+  )
+}
+
+/// Returns the UTF-16 little-endian bytes of a string
+fn utf16(s: &str) -> Vec<u8> {
+  s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::chromium_local_state_key;
+  use std::path::Path;
+
+  /// NT hash of `winpass`
+  const NT_HASH: [u8; 16] = [
+    0xee, 0x8b, 0x6c, 0x7a, 0xbe, 0x2b, 0xec, 0xe2, 0xac, 0xd4, 0x75, 0x9e, 0x48, 0xa2, 0x1c, 0x2e,
+  ];
+
+  fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("tests/fixtures/dpapi")
+      .join(name)
+  }
+
+  fn local_master_keys(password: &str) -> DpapiMasterKeys {
+    let dir = fixture("local/Protect/S-1-5-21-1-2-3-1001");
+    DpapiMasterKeys::new(dir, DpapiSecret::Password(password.to_string()))
+  }
+
+  #[test]
+  fn unprotects_blob() {
+    let master_keys = local_master_keys("winpass");
+    let blob = std::fs::read(fixture("local/blob")).unwrap();
+    assert_eq!(master_keys.unprotect(&blob).unwrap(), b"hello dpapi");
+
+    let key = chromium_local_state_key(fixture("local/Local State"), &master_keys).unwrap();
+    assert_eq!(key, (0..32).collect::<Vec<u8>>());
+  }
+
+  #[test]
+  fn unprotects_blob_signed_by_xp() {
+    let master_keys = local_master_keys("winpass");
+    let blob = std::fs::read(fixture("local/blob-xp")).unwrap();
+    assert_eq!(master_keys.unprotect(&blob).unwrap(), b"hello dpapi");
+  }
+
+  #[test]
+  fn unprotects_blob_of_domain_users() {
+    let dir = fixture("domain/Protect/S-1-5-21-9-8-7-1104");
+    let master_keys = DpapiMasterKeys::new(dir, DpapiSecret::NtHash(NT_HASH));
+    let key = chromium_local_state_key(fixture("domain/Local State"), &master_keys).unwrap();
+    assert_eq!(key, (0..32).collect::<Vec<u8>>());
+
+    // Protected Users, whose master key is derived with the PBKDF2 of the RFC
+    let dir = fixture("protected/Protect/S-1-5-21-9-8-7-1105");
+    let master_keys = DpapiMasterKeys::new(dir, DpapiSecret::Password("winpass".to_string()));
+    let key = chromium_local_state_key(fixture("protected/Local State"), &master_keys).unwrap();
+    assert_eq!(key, (0..32).collect::<Vec<u8>>());
+  }
+
+  #[test]
+  fn rejects_wrong_password() {
+    let master_keys = local_master_keys("wrong");
+    let blob = std::fs::read(fixture("local/blob")).unwrap();
+    let result = master_keys.unprotect(&blob);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+
+    // Right password, wrong SID
+    let master_keys = local_master_keys("winpass").sid("S-1-5-21-1-2-3-1002");
+    let result = master_keys.unprotect(&blob);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+  }
+
+  #[test]
+  fn rejects_tampered_blob() {
+    let master_keys = local_master_keys("winpass");
+    for name in ["local/blob", "local/blob-xp"] {
+      let mut blob = std::fs::read(fixture(name)).unwrap();
+      *blob.last_mut().unwrap() ^= 1;
+      let result = master_keys.unprotect(&blob);
+      assert!(
+        matches!(result, Err(RookieError::DecryptionFailed(_))),
+        "{}",
+        name
+      );
+    }
+  }
+}
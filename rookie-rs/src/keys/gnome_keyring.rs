@@ -0,0 +1,271 @@
+use super::{data_home, reader::Reader, KeyProvider, KeyRequest};
+use crate::RookieError;
+use eyre::{bail, Result};
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use std::{collections::HashMap, fmt, path::PathBuf};
+
+const MAGIC: &[u8] = b"GnomeKeyring\n\r\0\n";
+
+/// Attribute type of unsigned integers, the others are strings
+const ATTRIBUTE_UINT32: u32 = 1;
+
+/// Item of a GNOME keyring file
+pub(crate) struct Item {
+  pub attributes: HashMap<String, String>,
+  pub secret: Option<Vec<u8>>,
+}
+
+/// GNOME keyring file (`~/.local/share/keyrings/*.keyring`), unlocked with the password of the
+/// keyring, which is the login password for the login keyring
+///
+/// Reads the `Safe Storage` password without a Secret Service, such as on a headless server or
+/// from a disk image.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{
+///   chromium_based_offline,
+///   keys::{GnomeKeyringFile, KeyProvider, KeyRequest},
+///   ChromiumKeySource, ChromiumPlatform,
+/// };
+///
+/// let keyring = GnomeKeyringFile::new("/image/home/alice/.local/share/keyrings/login.keyring", "hunter2");
+/// let request = KeyRequest {
+///   browser: "chrome".to_string(),
+///   crypt_name: Some("chrome".to_string()),
+///   keychain_service: None,
+///   keychain_account: None,
+/// };
+/// let password = keyring.passwords(&request).unwrap().remove(0);
+/// let db_path = "/image/home/alice/.config/google-chrome/Default/Cookies";
+/// let source = ChromiumKeySource::Password(password);
+/// let cookies = chromium_based_offline(ChromiumPlatform::Linux, source, db_path.into(), None);
+/// ```
+#[derive(Clone)]
+pub struct GnomeKeyringFile {
+  path: PathBuf,
+  password: String,
+}
+
+impl GnomeKeyringFile {
+  pub fn new(path: impl Into<PathBuf>, password: impl Into<String>) -> Self {
+    Self {
+      path: path.into(),
+      password: password.into(),
+    }
+  }
+
+  /// Login keyring of the current user, `$XDG_DATA_HOME/keyrings/login.keyring`
+  pub fn login(password: impl Into<String>) -> Self {
+    Self::new(data_home().join("keyrings/login.keyring"), password)
+  }
+}
+
+impl fmt::Debug for GnomeKeyringFile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("GnomeKeyringFile")
+      .field("path", &self.path)
+      .finish_non_exhaustive()
+  }
+}
+
+impl KeyProvider for GnomeKeyringFile {
+  fn name(&self) -> &str {
+    "gnome keyring file"
+  }
+
+  fn passwords(&self, request: &KeyRequest) -> crate::Result<Vec<String>> {
+    let Some(crypt_name) = request.crypt_name.as_deref() else {
+      return Ok(vec![]);
+    };
+    let data = std::fs::read(&self.path)
+      .map_err(|e| eyre::Report::new(e).wrap_err(format!("Can't read {}", self.path.display())))?;
+    let items = read_items(&data, &self.password)?;
+    Ok(
+      items
+        .into_iter()
+        .filter(|item| is_chromium_password(item, crypt_name))
+        .filter_map(|item| String::from_utf8(item.secret?).ok())
+        .collect(),
+    )
+  }
+}
+
+/// Returns true if the item holds the `Safe Storage` password of the browser
+fn is_chromium_password(item: &Item, crypt_name: &str) -> bool {
+  let schema = item.attributes.get("xdg:schema");
+  item.attributes.get("application").map(String::as_str) == Some(crypt_name)
+    && schema.map_or(true, |schema| {
+      schema.starts_with("chrome_libsecret_os_crypt_password")
+    })
+}
+
+/// Returns the items of a keyring file, as written by gnome-keyring
+pub(crate) fn read_items(data: &[u8], password: &str) -> Result<Vec<Item>> {
+  let mut reader = Reader::new(data);
+  if reader.bytes(MAGIC.len())? != MAGIC {
+    bail!("Not a GNOME keyring file");
+  }
+  let version = (reader.u8()?, reader.u8()?);
+  let (crypto, hash) = (reader.u8()?, reader.u8()?);
+  if version != (0, 0) || crypto != 0 || hash != 0 {
+    return Err(
+      RookieError::UnsupportedSchema(format!(
+        "GNOME keyring version {}.{} (crypto {}, hash {})",
+        version.0, version.1, crypto, hash
+      ))
+      .into(),
+    );
+  }
+  read_string(&mut reader)?; // keyring name
+  reader.bytes(16)?; // creation and modification times
+  reader.u32()?; // flags
+  reader.u32()?; // lock timeout
+  let iterations = reader.u32()?;
+  let salt = reader.bytes(8)?;
+  reader.bytes(16)?; // reserved
+  let count = reader.u32()?;
+  // Items with their attributes hashed, readable while locked
+  for _ in 0..count {
+    reader.u32()?; // id
+    reader.u32()?; // type
+    read_attributes(&mut reader)?;
+  }
+  let size = reader.u32()? as usize;
+  let encrypted = reader.bytes(size)?;
+  let decrypted = decrypt(encrypted, password, salt, iterations)?;
+
+  let mut reader = Reader::new(&decrypted[16..]);
+  let mut items = vec![];
+  for _ in 0..count {
+    read_string(&mut reader)?; // display name
+    let secret = read_bytes(&mut reader)?;
+    reader.bytes(16)?; // creation and modification times
+    read_string(&mut reader)?; // reserved
+    reader.bytes(16)?; // reserved
+    let attributes = read_attributes(&mut reader)?.into_iter().collect();
+    // Access control list
+    for _ in 0..reader.u32()? {
+      reader.u32()?; // allowed types
+      read_string(&mut reader)?; // display name
+      read_string(&mut reader)?; // path name
+      read_string(&mut reader)?; // reserved
+      reader.u32()?; // reserved
+    }
+    items.push(Item { attributes, secret });
+  }
+  Ok(items)
+}
+
+/// Decrypts the items with AES-128-CBC, the data starts with the MD5 of the rest
+fn decrypt(encrypted: &[u8], password: &str, salt: &[u8], iterations: u32) -> Result<Vec<u8>> {
+  use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+
+  type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+  if encrypted.len() < 16 || encrypted.len() % 16 != 0 {
+    bail!("Invalid size of encrypted items: {}", encrypted.len());
+  }
+  let (key, iv) = derive_key(password, salt, iterations);
+  let cipher = Aes128CbcDec::new(&key.into(), &iv.into());
+  let mut data = encrypted.to_vec();
+  cipher
+    .decrypt_padded_mut::<NoPadding>(&mut data)
+    .map_err(|_| eyre::eyre!("Can't decrypt keyring items"))?;
+  if Md5::digest(&data[16..]).as_slice() != &data[..16] {
+    return Err(RookieError::KeyringLocked("Wrong password of GNOME keyring file".into()).into());
+  }
+  Ok(data)
+}
+
+/// Derives the AES key and IV from the password, hashing it `iterations` times with SHA-256
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> ([u8; 16], [u8; 16]) {
+  let mut digest = Sha256::new()
+    .chain_update(password.as_bytes())
+    .chain_update(salt)
+    .finalize();
+  for _ in 1..iterations {
+    digest = Sha256::digest(digest);
+  }
+  let mut key = [0u8; 16];
+  let mut iv = [0u8; 16];
+  key.copy_from_slice(&digest[..16]);
+  iv.copy_from_slice(&digest[16..]);
+  (key, iv)
+}
+
+/// Returns attributes by name, unsigned integers are formatted as strings
+fn read_attributes(reader: &mut Reader) -> Result<Vec<(String, String)>> {
+  let mut attributes = vec![];
+  for _ in 0..reader.u32()? {
+    let name = read_string(reader)?.unwrap_or_default();
+    let value = match reader.u32()? {
+      ATTRIBUTE_UINT32 => reader.u32()?.to_string(),
+      _ => read_string(reader)?.unwrap_or_default(),
+    };
+    attributes.push((name, value));
+  }
+  Ok(attributes)
+}
+
+/// Reads a length prefixed string, `0xffffffff` being a null string
+fn read_bytes(reader: &mut Reader) -> Result<Option<Vec<u8>>> {
+  match reader.u32()? {
+    u32::MAX => Ok(None),
+    len => Ok(Some(reader.bytes(len as usize)?.to_vec())),
+  }
+}
+
+fn read_string(reader: &mut Reader) -> Result<Option<String>> {
+  Ok(read_bytes(reader)?.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+
+  fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/keyrings/login.keyring")
+  }
+
+  fn request() -> KeyRequest {
+    KeyRequest {
+      browser: "chrome".to_string(),
+      crypt_name: Some("chrome".to_string()),
+      keychain_service: None,
+      keychain_account: None,
+    }
+  }
+
+  #[test]
+  fn reads_safe_storage_password() {
+    let keyring = GnomeKeyringFile::new(fixture(), "loginpw");
+    assert_eq!(keyring.passwords(&request()).unwrap(), ["ci-secret"]);
+
+    // The other item belongs to firefox
+    let items = read_items(&std::fs::read(fixture()).unwrap(), "loginpw").unwrap();
+    assert_eq!(items.len(), 2);
+  }
+
+  #[test]
+  fn rejects_wrong_password() {
+    let keyring = GnomeKeyringFile::new(fixture(), "wrong");
+    let result = keyring.passwords(&request());
+    assert!(matches!(result, Err(RookieError::KeyringLocked(_))));
+  }
+
+  #[test]
+  fn rejects_truncated_file() {
+    let data = std::fs::read(fixture()).unwrap();
+    for len in 0..data.len() {
+      assert!(
+        read_items(&data[..len], "loginpw").is_err(),
+        "{} bytes",
+        len
+      );
+    }
+  }
+}
@@ -0,0 +1,287 @@
+use super::{data_home, reader::Reader, KeyProvider, KeyRequest};
+use crate::RookieError;
+use eyre::{bail, Result};
+use sha1::{Digest, Sha1};
+use sha2::Sha512;
+use std::{collections::HashMap, fmt, path::PathBuf};
+
+const MAGIC: &[u8] = b"KWALLET\n\r\0\r\n";
+
+const CIPHER_BLOWFISH_ECB: u8 = 0;
+const CIPHER_BLOWFISH_CBC: u8 = 3;
+const HASH_PBKDF2_SHA512: u8 = 2;
+
+const PBKDF2_ITERATIONS: u32 = 50000;
+/// Size of the derived key, the largest Blowfish key
+const KEY_SIZE: usize = 56;
+const BLOCK_SIZE: usize = 8;
+
+/// Entry type of passwords, the others are streams and maps
+const ENTRY_PASSWORD: u32 = 1;
+
+/// KWallet file (`~/.local/share/kwalletd/*.kwl`), unlocked with the password of the wallet
+///
+/// Reads the `Safe Storage` password without a running kwalletd, such as from a copy of a KDE
+/// profile. The salt is read from the `.salt` file next to the wallet, only wallets hashed with
+/// PBKDF2 (KDE 4.13 and later) are supported.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{
+///   keys::{KWalletFile, KeyProviders},
+///   CookieQuery,
+/// };
+///
+/// let wallet = KWalletFile::new("/image/home/alice/.local/share/kwalletd/kdewallet.kwl", "hunter2");
+/// let cookies = CookieQuery::new()
+///   .browser("chrome")
+///   .key_providers(KeyProviders::new().with(wallet))
+///   .run();
+/// ```
+#[derive(Clone)]
+pub struct KWalletFile {
+  path: PathBuf,
+  password: String,
+}
+
+impl KWalletFile {
+  pub fn new(path: impl Into<PathBuf>, password: impl Into<String>) -> Self {
+    Self {
+      path: path.into(),
+      password: password.into(),
+    }
+  }
+
+  /// Default wallet of the current user, `$XDG_DATA_HOME/kwalletd/kdewallet.kwl`
+  pub fn kdewallet(password: impl Into<String>) -> Self {
+    Self::new(data_home().join("kwalletd/kdewallet.kwl"), password)
+  }
+}
+
+impl fmt::Debug for KWalletFile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("KWalletFile")
+      .field("path", &self.path)
+      .finish_non_exhaustive()
+  }
+}
+
+impl KeyProvider for KWalletFile {
+  fn name(&self) -> &str {
+    "kwallet file"
+  }
+
+  fn passwords(&self, request: &KeyRequest) -> crate::Result<Vec<String>> {
+    let Some(crypt_name) = request.crypt_name.as_deref() else {
+      return Ok(vec![]);
+    };
+    let salt_path = self.path.with_extension("salt");
+    let data = read(&self.path)?;
+    let salt = read(&salt_path)?;
+    let folders = read_folders(&data, &self.password, &salt)?;
+    for name in kdewallet_names(crypt_name) {
+      let password = folders
+        .get(&format!("{} Keys", name))
+        .and_then(|entries| entries.get(&format!("{} Safe Storage", name)));
+      if let Some(password) = password.filter(|password| !password.is_empty()) {
+        return Ok(vec![password.clone()]);
+      }
+    }
+    Ok(vec![])
+  }
+}
+
+fn read(path: &PathBuf) -> Result<Vec<u8>> {
+  std::fs::read(path)
+    .map_err(|e| eyre::Report::new(e).wrap_err(format!("Can't read {}", path.display())))
+}
+
+/// Returns the names of the folders holding the password, Chrome and Chromium builds use either
+/// `Chrome Keys` or `Chromium Keys`
+pub(crate) fn kdewallet_names(crypt_name: &str) -> Vec<String> {
+  let name = capitalize(crypt_name);
+  let mut names = vec![name.clone()];
+  match name.as_str() {
+    "Chrome" => names.push("Chromium".to_string()),
+    "Chromium" => names.push("Chrome".to_string()),
+    _ => {}
+  }
+  names
+}
+
+fn capitalize(s: &str) -> String {
+  let mut c = s.chars();
+  match c.next() {
+    None => String::new(),
+    Some(f) => f.to_uppercase().collect::<String>() + c.as_str(),
+  }
+}
+
+/// Returns the passwords of a wallet file by folder and key, as written by kwalletd
+pub(crate) fn read_folders(
+  data: &[u8],
+  password: &str,
+  salt: &[u8],
+) -> Result<HashMap<String, HashMap<String, String>>> {
+  let mut reader = Reader::new(data);
+  if reader.bytes(MAGIC.len())? != MAGIC {
+    bail!("Not a KWallet file");
+  }
+  let version = (reader.u8()?, reader.u8()?);
+  let (cipher, hash) = (reader.u8()?, reader.u8()?);
+  if version != (0, 1)
+    || !matches!(cipher, CIPHER_BLOWFISH_ECB | CIPHER_BLOWFISH_CBC)
+    || hash != HASH_PBKDF2_SHA512
+  {
+    return Err(
+      RookieError::UnsupportedSchema(format!(
+        "KWallet version {}.{} (cipher {}, hash {})",
+        version.0, version.1, cipher, hash
+      ))
+      .into(),
+    );
+  }
+  // MD5 of the folder and key names, readable while closed
+  for _ in 0..reader.u32()? {
+    reader.bytes(16)?; // folder
+    let count = reader.u32()? as usize;
+    reader.bytes(count.saturating_mul(16))?; // keys
+  }
+  let decrypted = decrypt(reader.rest(), password, salt, cipher)?;
+
+  let mut reader = Reader::new(&decrypted);
+  let mut folders = HashMap::new();
+  while !reader.is_empty() {
+    let folder = read_string(&mut reader)?;
+    let mut entries = HashMap::new();
+    for _ in 0..reader.u32()? {
+      let key = read_string(&mut reader)?;
+      let entry_type = reader.u32()?;
+      let value = read_bytes(&mut reader)?;
+      // Passwords are stored as serialized strings
+      if entry_type == ENTRY_PASSWORD {
+        entries.insert(key, read_string(&mut Reader::new(&value))?);
+      }
+    }
+    folders.insert(folder, entries);
+  }
+  Ok(folders)
+}
+
+/// Decrypts the wallet with Blowfish, the data is preceded by a random block and its size, and
+/// followed by random padding and its SHA-1
+fn decrypt(encrypted: &[u8], password: &str, salt: &[u8], cipher: u8) -> Result<Vec<u8>> {
+  use blowfish::{
+    cipher::{BlockDecrypt, KeyInit},
+    BlowfishLE,
+  };
+
+  // Random block, size and SHA-1 at least
+  if encrypted.len() < BLOCK_SIZE + 4 + 20 || encrypted.len() % BLOCK_SIZE != 0 {
+    bail!("Invalid size of encrypted wallet: {}", encrypted.len());
+  }
+  let mut key = [0u8; KEY_SIZE];
+  pbkdf2::pbkdf2_hmac::<Sha512>(password.as_bytes(), salt, PBKDF2_ITERATIONS, &mut key);
+  // kwalletd's Blowfish reads blocks as host order words, files are written on little endian
+  let blowfish =
+    BlowfishLE::new_from_slice(&key).map_err(|_| eyre::eyre!("Invalid wallet key size"))?;
+
+  let mut data = encrypted.to_vec();
+  // CBC with a zero IV, or ECB for wallets written by older kwalletd
+  let mut previous = [0u8; BLOCK_SIZE];
+  for block in data.chunks_exact_mut(BLOCK_SIZE) {
+    let ciphertext: [u8; BLOCK_SIZE] = (&*block).try_into()?;
+    blowfish.decrypt_block(block.into());
+    if cipher == CIPHER_BLOWFISH_CBC {
+      block
+        .iter_mut()
+        .zip(previous)
+        .for_each(|(byte, mask)| *byte ^= mask);
+      previous = ciphertext;
+    }
+  }
+
+  let mut reader = Reader::new(&data);
+  reader.bytes(BLOCK_SIZE)?; // random
+  let size = reader.u32()? as usize;
+  let hash_offset = data.len() - Sha1::output_size();
+  if size > hash_offset.saturating_sub(BLOCK_SIZE + 4) {
+    return Err(RookieError::KeyringLocked("Wrong password of KWallet file".into()).into());
+  }
+  let plaintext = reader.bytes(size)?;
+  if Sha1::digest(plaintext).as_slice() != &data[hash_offset..] {
+    return Err(RookieError::KeyringLocked("Wrong password of KWallet file".into()).into());
+  }
+  Ok(plaintext.to_vec())
+}
+
+/// Reads a `QByteArray`, `0xffffffff` being a null array
+fn read_bytes(reader: &mut Reader) -> Result<Vec<u8>> {
+  match reader.u32()? {
+    u32::MAX => Ok(vec![]),
+    len => Ok(reader.bytes(len as usize)?.to_vec()),
+  }
+}
+
+/// Reads a `QString`, UTF-16 big endian prefixed with its size in bytes
+fn read_string(reader: &mut Reader) -> Result<String> {
+  let bytes = read_bytes(reader)?;
+  let units: Vec<u16> = bytes
+    .chunks_exact(2)
+    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+    .collect();
+  Ok(String::from_utf16_lossy(&units))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+
+  fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("tests/fixtures/kwalletd")
+      .join(name)
+  }
+
+  fn request(crypt_name: &str) -> KeyRequest {
+    KeyRequest {
+      browser: crypt_name.to_string(),
+      crypt_name: Some(crypt_name.to_string()),
+      keychain_service: None,
+      keychain_account: None,
+    }
+  }
+
+  #[test]
+  fn reads_cbc_wallet() {
+    let data = std::fs::read(fixture("cbc.kwl")).unwrap();
+    let salt = std::fs::read(fixture("cbc.salt")).unwrap();
+    assert_eq!(data[MAGIC.len() + 2], CIPHER_BLOWFISH_CBC);
+    let folders = read_folders(&data, "walletpw", &salt).unwrap();
+    assert_eq!(folders["Chrome Keys"]["Chrome Safe Storage"], "ci-secret");
+  }
+
+  #[test]
+  fn reads_ecb_wallet() {
+    let data = std::fs::read(fixture("ecb.kwl")).unwrap();
+    assert_eq!(data[MAGIC.len() + 2], CIPHER_BLOWFISH_ECB);
+    // Only `Chromium Keys` is in the wallet, Chrome falls back to it
+    let wallet = KWalletFile::new(fixture("ecb.kwl"), "walletpw");
+    assert_eq!(wallet.passwords(&request("chrome")).unwrap(), ["ci-secret"]);
+  }
+
+  #[test]
+  fn rejects_wrong_password() {
+    for name in ["cbc.kwl", "ecb.kwl"] {
+      let wallet = KWalletFile::new(fixture(name), "wrong");
+      let result = wallet.passwords(&request("chrome"));
+      assert!(
+        matches!(result, Err(RookieError::KeyringLocked(_))),
+        "{}",
+        name
+      );
+    }
+  }
+}
@@ -0,0 +1,866 @@
+use crate::{
+  common::cancel::{CancelHandle, Deadline, POLL_INTERVAL},
+  config::Browser,
+  ChromiumPlatform, Result, RookieError,
+};
+use once_cell::sync::Lazy;
+use std::{
+  collections::HashMap,
+  fmt,
+  path::PathBuf,
+  sync::{Arc, Mutex, MutexGuard, RwLock, TryLockError},
+  thread,
+  time::{Duration, Instant},
+};
+
+mod dpapi;
+mod gnome_keyring;
+mod kwallet_file;
+mod reader;
+
+pub use dpapi::{DpapiMasterKeys, DpapiSecret};
+pub use gnome_keyring::GnomeKeyringFile;
+pub(crate) use kwallet_file::kdewallet_names;
+pub use kwallet_file::KWalletFile;
+
+static REGISTERED: Lazy<RwLock<KeyProviders>> = Lazy::new(|| RwLock::new(KeyProviders::platform()));
+
+/// How long a keyring without password, or which refused access, isn't asked again
+const MISSING_TTL: Duration = Duration::from_secs(60);
+
+/// Browser whose `Safe Storage` password is wanted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRequest {
+  /// Browser config name (`chrome`, `brave`, ...)
+  pub browser: String,
+  /// Application name of the password in the keyring (`chrome`, `chromium`, `brave`, ...)
+  pub crypt_name: Option<String>,
+  /// Keychain service of the password on macOS (`Chrome Safe Storage`, ...)
+  pub keychain_service: Option<String>,
+  /// Keychain account of the password on macOS (`Chrome`, ...)
+  pub keychain_account: Option<String>,
+}
+
+impl KeyRequest {
+  #[cfg_attr(target_os = "windows", allow(dead_code))]
+  pub(crate) fn new(browser: &str, config: &Browser) -> Self {
+    Self {
+      browser: browser.to_string(),
+      crypt_name: config.unix_crypt_name.clone(),
+      keychain_service: config.osx_key_service.clone(),
+      keychain_account: config.osx_key_user.clone(),
+    }
+  }
+
+  /// Name of the password in the keyring, shared by browsers which use the same password
+  fn keyring_name(&self) -> String {
+    match (&self.crypt_name, &self.keychain_service) {
+      (None, None) => format!("browser {}", self.browser),
+      (crypt_name, keychain_service) => format!(
+        "{}/{}",
+        crypt_name.as_deref().unwrap_or_default(),
+        keychain_service.as_deref().unwrap_or_default()
+      ),
+    }
+  }
+}
+
+/// Source of the `Safe Storage` password which chromium browsers encrypt cookies with on Linux
+/// and macOS
+///
+/// # Examples
+///
+/// ```
+/// use rookie::keys::{KeyProvider, KeyRequest};
+///
+/// struct Vault;
+///
+/// impl KeyProvider for Vault {
+///   fn name(&self) -> &str {
+///     "vault"
+///   }
+///
+///   fn passwords(&self, request: &KeyRequest) -> rookie::Result<Vec<String>> {
+///     Ok(match request.browser.as_str() {
+///       "chrome" => vec!["secret".to_string()],
+///       _ => vec![],
+///     })
+///   }
+/// }
+/// ```
+pub trait KeyProvider: Send + Sync {
+  /// Name shown in logs
+  fn name(&self) -> &str;
+
+  /// Returns the passwords of the browser, empty when the provider has none
+  fn passwords(&self, request: &KeyRequest) -> Result<Vec<String>>;
+}
+
+/// Ordered chain of key providers, the passwords of every provider are tried in order
+///
+/// A stale password of one keyring doesn't hide the right one of the next keyring, unless
+/// [`KeyProviders::first_match`] is set. Keys derived from the default passwords of chromium are
+/// always tried after them.
+/// A provider which doesn't answer within the timeout, or is cancelled, fails the query with
+/// [`RookieError::Timeout`] or [`RookieError::Cancelled`].
+/// Passwords found are cached by keyring name, along with the keys derived from them for each
+/// [`ChromiumPlatform`], so browsers sharing a password and later queries don't ask the keyring
+/// again (and don't trigger its unlock prompt again). A keyring without password, or which refused
+/// access, isn't asked again for a minute. Clones of a chain share its cache, adding a provider
+/// starts a new one. Queries asking for the same keyring name wait for each other, queries asking
+/// for other names don't.
+///
+/// # Examples
+///
+/// ```
+/// use rookie::keys::{self, EnvVar, KeyProviders, PasswordFile};
+///
+/// let providers = KeyProviders::new()
+///   .with(EnvVar::new("CHROME_SAFE_STORAGE"))
+///   .with(PasswordFile::new("/run/secrets/chrome_safe_storage"));
+/// keys::register(providers);
+/// ```
+#[derive(Clone)]
+pub struct KeyProviders {
+  providers: Vec<Arc<dyn KeyProvider>>,
+  cache: Arc<Mutex<HashMap<String, Slot>>>,
+  deadline: Deadline,
+  first_match: bool,
+}
+
+/// Cache entry of a keyring name, empty until the providers answered
+type Slot = Arc<Mutex<Option<Cached>>>;
+
+/// Outcome of asking the providers for a keyring name
+enum Cached {
+  /// Passwords found, along with the keys derived from them for each platform
+  Found {
+    passwords: Vec<String>,
+    keys: HashMap<ChromiumPlatform, Vec<Vec<u8>>>,
+  },
+  /// No password was found, with the message of the keyring which refused access if any
+  Missing {
+    locked: Option<String>,
+    since: Instant,
+  },
+}
+
+impl KeyProviders {
+  /// Creates an empty chain
+  pub fn new() -> Self {
+    Self {
+      providers: vec![],
+      cache: Arc::default(),
+      deadline: Deadline::default(),
+      first_match: false,
+    }
+  }
+
+  /// Creates the chain of the keyrings of the current platform
+  ///
+  /// Secret Service then KWallet on Linux, the keychain on macOS, nothing on Windows.
+  pub fn platform() -> Self {
+    #[allow(unused_mut)]
+    let mut providers = Self::new();
+    #[cfg(target_os = "linux")]
+    {
+      providers = providers.with(Libsecret::new()).with(KWallet);
+    }
+    #[cfg(target_os = "macos")]
+    {
+      providers = providers.with(MacKeychain);
+    }
+    providers
+  }
+
+  /// Appends a provider, tried after the previous ones
+  pub fn with(mut self, provider: impl KeyProvider + 'static) -> Self {
+    self.providers.push(Arc::new(provider));
+    // Passwords of the new chain differ, the cache of the previous one is left as is
+    self.cache = Arc::default();
+    self
+  }
+
+  /// Whether to stop at the first provider which has passwords instead of asking every provider
+  /// (default false)
+  ///
+  /// Saves asking the next keyrings, which may show their unlock prompt, at the cost of failing
+  /// when the first password is stale.
+  pub fn first_match(mut self, first_match: bool) -> Self {
+    self.first_match = first_match;
+    self.cache = Arc::default();
+    self
+  }
+
+  /// Sets how long the providers have to answer all together, including the unlock prompts they
+  /// show
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.deadline.timeout = Some(timeout);
+    self
+  }
+
+  /// Sets a handle cancelling the provider being asked
+  pub fn cancel_handle(mut self, cancel: CancelHandle) -> Self {
+    self.deadline.cancel = Some(cancel);
+    self
+  }
+
+  /// Replaces the timeout and cancel handle, unless the deadline has none
+  pub(crate) fn with_deadline(mut self, deadline: &Deadline) -> Self {
+    if !deadline.is_unlimited() {
+      self.deadline = deadline.clone();
+    }
+    self
+  }
+
+  /// Forgets the cached passwords and keys, the providers are asked again on the next query
+  pub fn clear_cache(&self) {
+    self.cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.providers.is_empty()
+  }
+
+  /// Returns the passwords of the providers without duplicates, along with the keyring error
+  /// which prevented reading any, if none was found
+  #[cfg_attr(target_os = "windows", allow(dead_code))]
+  pub(crate) fn passwords(
+    &self,
+    request: &KeyRequest,
+  ) -> Result<(Vec<String>, Option<RookieError>)> {
+    let deadline = self.deadline.start();
+    let slot = self.slot(request);
+    let mut cached = lock_slot(&slot, &deadline, request)?;
+    match &*cached {
+      Some(Cached::Found { passwords, .. }) => {
+        log::debug!("Using cached password of {}", request.keyring_name());
+        return Ok((passwords.clone(), None));
+      }
+      Some(Cached::Missing { locked, since }) if since.elapsed() < MISSING_TTL => {
+        log::debug!("No password of {} was found lately", request.keyring_name());
+        return Ok((vec![], locked.clone().map(RookieError::KeyringLocked)));
+      }
+      _ => {}
+    }
+    let (passwords, locked_error) = self.ask(request, &deadline)?;
+    if passwords.is_empty() {
+      let locked = match &locked_error {
+        Some(RookieError::KeyringLocked(message)) => Some(message.clone()),
+        _ => None,
+      };
+      *cached = Some(Cached::Missing {
+        locked,
+        since: Instant::now(),
+      });
+      return Ok((passwords, locked_error));
+    }
+    if let Some(locked_error) = locked_error {
+      log::warn!(
+        "Some passwords of {} are missing: {}",
+        request.browser,
+        locked_error
+      );
+    }
+    *cached = Some(Cached::Found {
+      passwords: passwords.clone(),
+      keys: HashMap::new(),
+    });
+    Ok((passwords, None))
+  }
+
+  /// Returns the keys `derive` makes of the passwords of the providers, along with the keyring
+  /// error which prevented reading any password, if none was found
+  ///
+  /// Keys derived from passwords found are cached for the platform.
+  #[cfg_attr(target_os = "windows", allow(dead_code))]
+  pub(crate) fn keys(
+    &self,
+    request: &KeyRequest,
+    platform: ChromiumPlatform,
+    derive: impl FnOnce(&[String]) -> Vec<Vec<u8>>,
+  ) -> Result<(Vec<Vec<u8>>, Option<RookieError>)> {
+    let (passwords, keyring_error) = self.passwords(request)?;
+    if passwords.is_empty() {
+      return Ok((derive(&passwords), keyring_error));
+    }
+    let slot = self.slot(request);
+    let mut cached = lock_slot(&slot, &self.deadline.start(), request)?;
+    let keys = match &mut *cached {
+      // Unless the cache was cleared meanwhile
+      Some(Cached::Found {
+        passwords: cached_passwords,
+        keys,
+      }) if *cached_passwords == passwords => keys
+        .entry(platform)
+        .or_insert_with(|| derive(&passwords))
+        .clone(),
+      _ => derive(&passwords),
+    };
+    Ok((keys, keyring_error))
+  }
+
+  /// Returns the cache slot of the keyring name of the request
+  fn slot(&self, request: &KeyRequest) -> Slot {
+    let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+    cache.entry(request.keyring_name()).or_default().clone()
+  }
+
+  /// Asks every provider, or the first one with passwords when `first_match` is set
+  fn ask(
+    &self,
+    request: &KeyRequest,
+    deadline: &Deadline,
+  ) -> Result<(Vec<String>, Option<RookieError>)> {
+    let mut passwords: Vec<String> = vec![];
+    let mut locked_error = None;
+    for provider in &self.providers {
+      let operation = format!(
+        "Reading password of {} from {}",
+        request.browser,
+        provider.name()
+      );
+      let asked = {
+        let provider = provider.clone();
+        let request = request.clone();
+        deadline
+          .run(&operation, move || Ok(provider.passwords(&request)?))
+          .map_err(RookieError::from)
+      };
+      match asked {
+        Ok(found) if !found.is_empty() => {
+          log::debug!(
+            "Using password of {} from {}",
+            request.browser,
+            provider.name()
+          );
+          for password in found {
+            if !passwords.contains(&password) {
+              passwords.push(password);
+            }
+          }
+          if self.first_match {
+            break;
+          }
+        }
+        Ok(_) => log::debug!("{} has no password of {}", provider.name(), request.browser),
+        Err(e @ (RookieError::Timeout { .. } | RookieError::Cancelled(_))) => return Err(e),
+        Err(e) => {
+          log::debug!("{} failed: {}", provider.name(), e);
+          if locked_error.is_none() && matches!(e, RookieError::KeyringLocked(_)) {
+            locked_error = Some(e);
+          }
+        }
+      }
+    }
+    Ok((passwords, locked_error))
+  }
+}
+
+/// Locks the cache slot of a keyring name, held while asking the providers so concurrent queries
+/// wait for a single unlock prompt, within their own deadline
+fn lock_slot<'a>(
+  slot: &'a Mutex<Option<Cached>>,
+  deadline: &Deadline,
+  request: &KeyRequest,
+) -> Result<MutexGuard<'a, Option<Cached>>> {
+  loop {
+    match slot.try_lock() {
+      Ok(cached) => return Ok(cached),
+      Err(TryLockError::Poisoned(e)) => return Ok(e.into_inner()),
+      Err(TryLockError::WouldBlock) => {
+        deadline.check(&format!("Waiting for the password of {}", request.browser))?;
+        thread::sleep(POLL_INTERVAL);
+      }
+    }
+  }
+}
+
+impl Default for KeyProviders {
+  /// Same as [`KeyProviders::platform`]
+  fn default() -> Self {
+    Self::platform()
+  }
+}
+
+impl fmt::Debug for KeyProviders {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("KeyProviders")
+      .field(
+        "providers",
+        &self
+          .providers
+          .iter()
+          .map(|provider| provider.name())
+          .collect::<Vec<_>>(),
+      )
+      .field("deadline", &self.deadline)
+      .finish()
+  }
+}
+
+/// Sets the key providers used by every query of the process which doesn't set its own
+pub fn register(providers: KeyProviders) {
+  *REGISTERED.write().unwrap_or_else(|e| e.into_inner()) = providers;
+}
+
+/// Returns the registered key providers, the ones of the platform when none were registered
+///
+/// The returned chain shares the cache of the registered one.
+pub fn registered() -> KeyProviders {
+  REGISTERED.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Forgets the passwords cached by the registered key providers
+///
+/// Call it after the password of a browser changed, or to lock the keyring again.
+///
+/// # Examples
+///
+/// ```
+/// let cookies = rookie::chrome(None);
+/// // The keyring isn't asked again
+/// let cookies = rookie::chrome(None);
+/// rookie::keys::clear_key_cache();
+/// ```
+pub fn clear_key_cache() {
+  registered().clear_cache();
+}
+
+/// Data directory of the current user, `$XDG_DATA_HOME` or `~/.local/share`
+fn data_home() -> PathBuf {
+  std::env::var_os("XDG_DATA_HOME")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| {
+      PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local/share")
+    })
+}
+
+/// Secret Service (GNOME keyring, KeePassXC, ...), the `chrome_libsecret_os_crypt_password`
+/// items of the browser
+///
+/// Secrets are transferred encrypted when the service supports it. A locked keyring shows its
+/// unlock prompt, which the user has 30 seconds to answer by default.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy)]
+pub struct Libsecret {
+  prompt_timeout: Duration,
+}
+
+#[cfg(target_os = "linux")]
+impl Libsecret {
+  pub fn new() -> Self {
+    Self {
+      prompt_timeout: Duration::from_secs(30),
+    }
+  }
+
+  /// Sets how long the user has to answer the unlock prompt, zero fails right away instead of
+  /// prompting
+  pub fn prompt_timeout(mut self, timeout: Duration) -> Self {
+    self.prompt_timeout = timeout;
+    self
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl Default for Libsecret {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl KeyProvider for Libsecret {
+  fn name(&self) -> &str {
+    "libsecret"
+  }
+
+  fn passwords(&self, request: &KeyRequest) -> Result<Vec<String>> {
+    let crypt_name = request.crypt_name.as_deref().unwrap_or_default();
+    Ok(crate::linux::get_passwords_libsecret(
+      crypt_name,
+      self.prompt_timeout,
+    )?)
+  }
+}
+
+/// KDE Wallet, the `<Browser> Safe Storage` entry of the network wallet
+///
+/// The daemon of Plasma 6 is used first, then the ones of Plasma 5 and KDE 4.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KWallet;
+
+#[cfg(target_os = "linux")]
+impl KWallet {
+  /// Returns the KWallet daemon of the session, if any
+  pub fn backend() -> Option<KWalletBackend> {
+    let connection = zbus::blocking::Connection::session().ok()?;
+    crate::linux::kwallet_backend(&connection)
+  }
+
+  /// Returns the password of a browser along with the daemon it was read from
+  pub fn read_password(&self, request: &KeyRequest) -> Result<(String, KWalletBackend)> {
+    let crypt_name = request.crypt_name.as_deref().unwrap_or_default();
+    Ok(crate::linux::get_password_kdewallet(crypt_name)?)
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl KeyProvider for KWallet {
+  fn name(&self) -> &str {
+    "kwallet"
+  }
+
+  fn passwords(&self, request: &KeyRequest) -> Result<Vec<String>> {
+    let (password, backend) = self.read_password(request)?;
+    log::info!("Read password of {} from {}", request.browser, backend);
+    Ok(vec![password])
+  }
+}
+
+/// KWallet daemon, as found on the session bus
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KWalletBackend {
+  /// `kwalletd6` of Plasma 6
+  KWalletd6,
+  /// `kwalletd5` of Plasma 5
+  KWalletd5,
+  /// `kwalletd` of KDE 4, on the bus as `org.kde.kwalletd`
+  KWalletd4,
+}
+
+#[cfg(target_os = "linux")]
+impl KWalletBackend {
+  pub(crate) const ALL: [KWalletBackend; 3] = [
+    KWalletBackend::KWalletd6,
+    KWalletBackend::KWalletd5,
+    KWalletBackend::KWalletd4,
+  ];
+
+  /// D-Bus name of the daemon
+  pub fn service(&self) -> &'static str {
+    match self {
+      KWalletBackend::KWalletd6 => "org.kde.kwalletd6",
+      KWalletBackend::KWalletd5 => "org.kde.kwalletd5",
+      KWalletBackend::KWalletd4 => "org.kde.kwalletd",
+    }
+  }
+
+  /// D-Bus object path of the wallet interface
+  pub fn path(&self) -> &'static str {
+    match self {
+      KWalletBackend::KWalletd6 => "/modules/kwalletd6",
+      KWalletBackend::KWalletd5 => "/modules/kwalletd5",
+      KWalletBackend::KWalletd4 => "/modules/kwalletd",
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl fmt::Display for KWalletBackend {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.service())
+  }
+}
+
+/// macOS keychain, through `/usr/bin/security`
+#[cfg(target_os = "macos")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MacKeychain;
+
+#[cfg(target_os = "macos")]
+impl KeyProvider for MacKeychain {
+  fn name(&self) -> &str {
+    "keychain"
+  }
+
+  fn passwords(&self, request: &KeyRequest) -> Result<Vec<String>> {
+    let (Some(service), Some(account)) = (&request.keychain_service, &request.keychain_account)
+    else {
+      return Ok(vec![]);
+    };
+    let password = crate::macos::get_osx_keychain_password(service, account)
+      .map_err(|e| RookieError::KeyringLocked(e.to_string()))?;
+    Ok(vec![password])
+  }
+}
+
+/// Environment variable holding the password, for every browser
+#[derive(Debug, Clone)]
+pub struct EnvVar {
+  name: String,
+}
+
+impl EnvVar {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self { name: name.into() }
+  }
+}
+
+impl KeyProvider for EnvVar {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn passwords(&self, _request: &KeyRequest) -> Result<Vec<String>> {
+    Ok(
+      std::env::var(&self.name)
+        .ok()
+        .filter(|password| !password.is_empty())
+        .into_iter()
+        .collect(),
+    )
+  }
+}
+
+/// File holding the password, for every browser
+///
+/// A trailing line break is ignored.
+#[derive(Debug, Clone)]
+pub struct PasswordFile {
+  path: PathBuf,
+}
+
+impl PasswordFile {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Self { path: path.into() }
+  }
+}
+
+impl KeyProvider for PasswordFile {
+  fn name(&self) -> &str {
+    "password file"
+  }
+
+  fn passwords(&self, _request: &KeyRequest) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(&self.path)
+      .map_err(|e| eyre::Report::new(e).wrap_err(format!("Can't read {}", self.path.display())))?;
+    let password = content.trim_end_matches(['\r', '\n']);
+    Ok(
+      Some(password)
+        .filter(|password| !password.is_empty())
+        .map(str::to_string)
+        .into_iter()
+        .collect(),
+    )
+  }
+}
+
+/// Closure returning the password of a browser
+///
+/// # Examples
+///
+/// ```
+/// use rookie::keys::{Callback, KeyProviders};
+///
+/// let providers = KeyProviders::new().with(Callback::new(|request| {
+///   (request.browser == "chrome").then(|| "secret".to_string())
+/// }));
+/// ```
+pub struct Callback {
+  callback: Box<PasswordCallback>,
+}
+
+type PasswordCallback = dyn Fn(&KeyRequest) -> Option<String> + Send + Sync;
+
+impl Callback {
+  pub fn new(callback: impl Fn(&KeyRequest) -> Option<String> + Send + Sync + 'static) -> Self {
+    Self {
+      callback: Box::new(callback),
+    }
+  }
+}
+
+impl KeyProvider for Callback {
+  fn name(&self) -> &str {
+    "callback"
+  }
+
+  fn passwords(&self, request: &KeyRequest) -> Result<Vec<String>> {
+    Ok((self.callback)(request).into_iter().collect())
+  }
+}
+
+/// Password known in advance, for every browser
+#[derive(Debug, Clone)]
+pub struct StaticPassword {
+  password: String,
+}
+
+impl StaticPassword {
+  pub fn new(password: impl Into<String>) -> Self {
+    Self {
+      password: password.into(),
+    }
+  }
+}
+
+impl KeyProvider for StaticPassword {
+  fn name(&self) -> &str {
+    "static password"
+  }
+
+  fn passwords(&self, _request: &KeyRequest) -> Result<Vec<String>> {
+    Ok(vec![self.password.clone()])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  struct Locked;
+
+  impl KeyProvider for Locked {
+    fn name(&self) -> &str {
+      "locked"
+    }
+
+    fn passwords(&self, _request: &KeyRequest) -> Result<Vec<String>> {
+      Err(RookieError::KeyringLocked("locked".to_string()))
+    }
+  }
+
+  fn request() -> KeyRequest {
+    KeyRequest {
+      browser: "chrome".to_string(),
+      crypt_name: Some("chrome".to_string()),
+      keychain_service: None,
+      keychain_account: None,
+    }
+  }
+
+  #[test]
+  fn asks_every_provider() {
+    let providers = KeyProviders::new()
+      .with(StaticPassword::new("stale"))
+      .with(Locked)
+      .with(Callback::new(|_| Some("right".to_string())))
+      .with(StaticPassword::new("stale"));
+    let (passwords, error) = providers.passwords(&request()).unwrap();
+    assert_eq!(passwords, ["stale", "right"]);
+    assert!(error.is_none());
+
+    let (passwords, _) = providers.first_match(true).passwords(&request()).unwrap();
+    assert_eq!(passwords, ["stale"]);
+  }
+
+  #[test]
+  fn chains_have_their_own_cache() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let providers = KeyProviders::new().with(Callback::new(move |_| {
+      counted.fetch_add(1, Ordering::SeqCst);
+      Some("first".to_string())
+    }));
+    providers.passwords(&request()).unwrap();
+
+    let extended = providers.clone().with(StaticPassword::new("second"));
+    let (passwords, _) = extended.passwords(&request()).unwrap();
+    assert_eq!(passwords, ["first", "second"]);
+    // The first chain still has its password cached
+    providers.passwords(&request()).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    providers.clear_cache();
+    providers.passwords(&request()).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn caches_missing_passwords() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let providers = KeyProviders::new().with(Callback::new(move |_| {
+      counted.fetch_add(1, Ordering::SeqCst);
+      None
+    }));
+    for _ in 0..2 {
+      let (passwords, error) = providers.passwords(&request()).unwrap();
+      assert!(passwords.is_empty());
+      assert!(error.is_none());
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    providers.clear_cache();
+    providers.passwords(&request()).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+  }
+
+  #[test]
+  fn caches_derived_keys() {
+    let providers = KeyProviders::new().with(StaticPassword::new("secret"));
+    let derived = AtomicUsize::new(0);
+    let derive = |passwords: &[String]| {
+      derived.fetch_add(1, Ordering::SeqCst);
+      passwords
+        .iter()
+        .map(|password| password.clone().into_bytes())
+        .collect()
+    };
+    for platform in [
+      ChromiumPlatform::Linux,
+      ChromiumPlatform::Linux,
+      ChromiumPlatform::MacOs,
+    ] {
+      let (keys, _) = providers.keys(&request(), platform, derive).unwrap();
+      assert_eq!(keys, [b"secret".to_vec()]);
+    }
+    assert_eq!(derived.load(Ordering::SeqCst), 2);
+
+    providers.clear_cache();
+    providers
+      .keys(&request(), ChromiumPlatform::Linux, derive)
+      .unwrap();
+    assert_eq!(derived.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn waits_only_for_the_same_keyring() {
+    let (release, released) = std::sync::mpsc::channel::<()>();
+    let released = Mutex::new(released);
+    let providers = KeyProviders::new().with(Callback::new(move |request| {
+      if request.browser == "chrome" {
+        // Unlock prompt left open
+        let _ = released.lock().unwrap().recv();
+      }
+      Some("secret".to_string())
+    }));
+    let prompting = {
+      let providers = providers.clone();
+      thread::spawn(move || providers.passwords(&request()))
+    };
+    thread::sleep(POLL_INTERVAL);
+
+    let other = KeyRequest {
+      browser: "brave".to_string(),
+      crypt_name: Some("brave".to_string()),
+      ..request()
+    };
+    let (passwords, _) = providers.passwords(&other).unwrap();
+    assert_eq!(passwords, ["secret"]);
+
+    let waiting = providers.clone().timeout(Duration::from_millis(100));
+    let result = waiting.passwords(&request());
+    assert!(matches!(result, Err(RookieError::Timeout { .. })));
+
+    release.send(()).unwrap();
+    let (passwords, _) = prompting.join().unwrap().unwrap();
+    assert_eq!(passwords, ["secret"]);
+  }
+
+  #[test]
+  fn reports_locked_keyring() {
+    let providers = KeyProviders::new().with(Locked);
+    // Also when cached
+    for _ in 0..2 {
+      let (passwords, error) = providers.passwords(&request()).unwrap();
+      assert!(passwords.is_empty());
+      assert!(matches!(error, Some(RookieError::KeyringLocked(_))));
+    }
+  }
+}
@@ -0,0 +1,62 @@
+use eyre::{bail, Result};
+
+/// Reads the fields of keyring files, big-endian unless stated otherwise
+pub(crate) struct Reader<'a> {
+  data: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(data: &'a [u8]) -> Self {
+    Self { data, offset: 0 }
+  }
+
+  pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+    let end = self.offset.saturating_add(len);
+    let Some(bytes) = self.data.get(self.offset..end) else {
+      bail!("Unexpected end of file at offset {}", self.offset);
+    };
+    self.offset = end;
+    Ok(bytes)
+  }
+
+  /// Returns the bytes which weren't read yet
+  pub fn rest(&mut self) -> &'a [u8] {
+    let rest = &self.data[self.offset..];
+    self.offset = self.data.len();
+    rest
+  }
+
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.offset >= self.data.len()
+  }
+
+  pub fn u8(&mut self) -> Result<u8> {
+    Ok(self.bytes(1)?[0])
+  }
+
+  pub fn u32(&mut self) -> Result<u32> {
+    let bytes = self.bytes(4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  pub fn u32_le(&mut self) -> Result<u32> {
+    let bytes = self.bytes(4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  pub fn u64_le(&mut self) -> Result<u64> {
+    let bytes: [u8; 8] = self.bytes(8)?.try_into()?;
+    Ok(u64::from_le_bytes(bytes))
+  }
+
+  /// Reads bytes prefixed with their little-endian size
+  pub fn sized_le(&mut self) -> Result<&'a [u8]> {
+    let len = self.u32_le()? as usize;
+    self.bytes(len)
+  }
+}
@@ -3,27 +3,37 @@
 // Common
 pub mod common;
 pub mod config;
+pub mod error;
+pub mod keys;
+pub mod os_crypt;
+pub mod profiles;
+pub mod query;
+pub mod report;
 mod utils;
 pub use common::enums;
+pub use error::{Result, RookieError};
+pub use profiles::{list_profiles, Profile, ProfileSelector};
+pub use query::{CookieFile, CookieQuery};
+pub use report::{BrowserReport, LoadStatus};
 
 // Browser
 #[cfg(target_os = "windows")]
 pub use browser::internet_explorer::internet_explorer_based;
 #[cfg(target_os = "macos")]
 pub use browser::safari::safari_based;
-pub use browser::{chromium::chromium_based, mozilla::firefox_based};
+pub use browser::{
+  chromium::{
+    chromium_based, chromium_based_offline, chromium_local_state_key, ChromiumKeySource,
+    ChromiumPlatform,
+  },
+  mozilla::firefox_based,
+};
 
 // Private
 mod browser;
-use common::paths;
-use config::get_browser_config;
 use enums::Cookie;
-use eyre::bail;
-pub use eyre::Result;
 #[cfg(target_os = "linux")]
 mod linux;
-#[cfg(target_os = "windows")]
-use std::path::PathBuf;
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "windows")]
@@ -42,6 +52,15 @@ pub fn version() -> String {
   format!("{} ({})", env!("CARGO_PKG_VERSION"), env!("COMMIT_HASH"))
 }
 
+/// Returns cookies from a browser by its config name
+fn browser_cookies(name: &str, domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
+  let mut query = CookieQuery::new().browser(name);
+  if let Some(domains) = domains {
+    query = query.domains(domains);
+  }
+  query.run()
+}
+
 /// Returns cookies from Firefox
 ///
 /// # Arguments
@@ -51,13 +70,11 @@ pub fn version() -> String {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::firefox(Some(domains));
 /// ```
 pub fn firefox(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("firefox");
-  let db_path = paths::find_mozilla_based_paths(config)?;
-  firefox_based(db_path, domains)
+  browser_cookies("firefox", domains)
 }
 
 /// Returns cookies from LibreWolf
@@ -69,13 +86,11 @@ pub fn firefox(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::librewolf(Some(domains));
 /// ```
 pub fn librewolf(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("librewolf");
-  let db_path = paths::find_mozilla_based_paths(config)?;
-  firefox_based(db_path, domains)
+  browser_cookies("librewolf", domains)
 }
 
 /// Returns cookies from Cachy Browser (Linux only)
@@ -87,14 +102,12 @@ pub fn librewolf(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::cachy(Some(domains));
 /// ```
 #[cfg(target_os = "linux")]
 pub fn cachy(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("cachy");
-  let db_path = paths::find_mozilla_based_paths(config)?;
-  firefox_based(db_path, domains)
+  browser_cookies("cachy", domains)
 }
 
 /// Returns cookies from Chrome
@@ -106,21 +119,11 @@ pub fn cachy(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::chrome(Some(domains));
 /// ```
 pub fn chrome(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("chrome");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("chrome", domains)
 }
 
 /// Returns cookies from Chromium
@@ -132,21 +135,11 @@ pub fn chrome(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::chromium(Some(domains));
 /// ```
 pub fn chromium(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("chromium");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("chromium", domains)
 }
 
 /// Returns cookies from Brave
@@ -158,21 +151,11 @@ pub fn chromium(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::brave(Some(domains));
 /// ```
 pub fn brave(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("brave");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("brave", domains)
 }
 
 /// Returns cookies from Arc
@@ -184,21 +167,11 @@ pub fn brave(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::brave(Some(domains));
 /// ```
 pub fn arc(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("arc");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("arc", domains)
 }
 
 /// Returns cookies from Firefox
@@ -210,13 +183,11 @@ pub fn arc(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::zen(Some(domains));
 /// ```
 pub fn zen(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("zen");
-  let db_path = paths::find_mozilla_based_paths(config)?;
-  firefox_based(db_path, domains)
+  browser_cookies("zen", domains)
 }
 
 /// Returns cookies from Edge
@@ -228,21 +199,11 @@ pub fn zen(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::edge(Some(domains));
 /// ```
 pub fn edge(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("edge");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("edge", domains)
 }
 
 /// Returns cookies from Vivaldi
@@ -254,21 +215,11 @@ pub fn edge(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::vivaldi(Some(domains));
 /// ```
 pub fn vivaldi(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("vivaldi");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("vivaldi", domains)
 }
 
 /// Returns cookies from Opera
@@ -280,21 +231,11 @@ pub fn vivaldi(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::opera(Some(domains));
 /// ```
 pub fn opera(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("opera");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("opera", domains)
 }
 
 /// Returns cookies from Opera GX
@@ -306,21 +247,11 @@ pub fn opera(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::opera_gx(Some(domains));
 /// ```
 pub fn opera_gx(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("opera_gx");
-  #[cfg(target_os = "windows")]
-  {
-    let (key, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(key, db_path, domains)
-  }
-  #[cfg(unix)]
-  {
-    let (_, db_path) = paths::find_chrome_based_paths(config)?;
-    chromium_based(config, db_path, domains)
-  }
+  browser_cookies("opera_gx", domains)
 }
 
 /// Returns cookies from Octo Browser
@@ -332,14 +263,12 @@ pub fn opera_gx(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::octo_browser(Some(domains));
 /// ```
 #[cfg(target_os = "windows")]
 pub fn octo_browser(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("octo_browser");
-  let (key, db_path) = paths::find_chrome_based_paths(config)?;
-  chromium_based(key, db_path, domains)
+  browser_cookies("octo_browser", domains)
 }
 
 /// Returns cookies from Safari (macOS only)
@@ -351,14 +280,12 @@ pub fn octo_browser(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::safari(Some(domains));
 /// ```
 #[cfg(target_os = "macos")]
 pub fn safari(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("safari");
-  let db_path = paths::find_safari_based_paths(config)?;
-  safari_based(db_path, domains)
+  browser_cookies("safari", domains)
 }
 
 /// Returns cookies from Internet Explorer (Windows only)
@@ -370,14 +297,12 @@ pub fn safari(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::internet_explorer(Some(domains));
 /// ```
 #[cfg(target_os = "windows")]
 pub fn internet_explorer(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let config = get_browser_config("ie");
-  let db_path = paths::find_ie_based_paths(config)?;
-  internet_explorer_based(db_path, domains)
+  browser_cookies("ie", domains)
 }
 
 /// Returns cookies from all browsers
@@ -389,40 +314,42 @@ pub fn internet_explorer(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 /// # Examples
 ///
 /// ```
-/// let domains = vec!["google.com"];
+/// let domains = vec!["google.com".to_string()];
 /// let cookies = rookie::load(Some(domains));
 /// ```
 pub fn load(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
-  let mut cookies = Vec::new();
-
-  let mut browser_types = vec![
-    firefox, zen, librewolf, opera, edge, chromium, brave, vivaldi, arc,
-  ];
-
-  #[cfg(target_os = "windows")]
-  {
-    browser_types.push(chrome);
-    browser_types.push(internet_explorer);
-    browser_types.push(opera_gx);
-  }
-  #[cfg(target_os = "linux")]
-  {
-    browser_types.push(chrome);
-    browser_types.push(cachy);
-  }
-  #[cfg(target_os = "macos")]
-  {
-    browser_types.push(chrome);
-    browser_types.push(opera_gx);
-    browser_types.push(safari);
+  let mut query = CookieQuery::new();
+  if let Some(domains) = domains {
+    query = query.domains(domains);
   }
+  query.run()
+}
 
-  for browser_fn in browser_types.iter() {
-    let browser_cookies = browser_fn(domains.clone()).unwrap_or(vec![]);
-    cookies.extend(browser_cookies);
+/// Returns cookies from all browsers along with the outcome of every browser
+///
+/// Unlike [`load`], tells apart browsers which aren't installed from browsers which failed.
+///
+/// # Arguments
+///
+/// * `domains` - A optional list that for getting specific domains only
+///
+/// # Examples
+///
+/// ```
+/// use rookie::LoadStatus;
+///
+/// for report in rookie::load_with_report(None) {
+///   if let LoadStatus::Failed(e) = &report.status {
+///     println!("{} failed: {}", report.browser, e);
+///   }
+/// }
+/// ```
+pub fn load_with_report(domains: Option<Vec<String>>) -> Vec<BrowserReport> {
+  let mut query = CookieQuery::new();
+  if let Some(domains) = domains {
+    query = query.domains(domains);
   }
-
-  Ok(cookies)
+  query.run_with_report()
 }
 
 /// Returns cookies from specific browser
@@ -432,12 +359,13 @@ pub fn load(domains: Option<Vec<String>>) -> Result<Vec<Cookie>> {
 ///
 /// * `cookies_path` - Absolute path for cookies file
 /// * `domains` - Optional list that for getting specific domains only
-/// * `key_path` - Optional absolute path for key required to decrypt the cookies (required for chrome)
+/// * `key_path` - Optional absolute path for key required to decrypt the cookies, chromium based
+///   browsers on Windows search `Local State` next to the cookies file without it
 ///
 /// # Examples
 ///
-/// ```
-/// let domains = vec!["google.com"];
+/// ```no_run
+/// let domains = vec!["google.com".to_string()];
 /// let cookies_path = "C:\\Users\\User\\AppData\\Local\\BraveSoftware\\Brave-Browser\\User Data\\default\\network\\Cookies";
 /// let key_path = "C:\\Users\\User\\AppData\\Local\\BraveSoftware\\Brave-Browser\\User Data\\Local State";
 /// let cookies = rookie::any_browser(cookies_path, None, Some(key_path)).unwrap();
@@ -451,31 +379,24 @@ pub fn any_browser(
   // chromium based
   #[cfg(unix)]
   {
-    let chrome_configs = &[
-      get_browser_config("chrome"),
-      get_browser_config("brave"),
-      get_browser_config("chromium"),
-      get_browser_config("edge"),
-      get_browser_config("opera"),
-      get_browser_config("opera_gx"),
-      get_browser_config("vivaldi"),
+    let chrome_names = [
+      "chrome", "brave", "chromium", "edge", "opera", "opera_gx", "vivaldi",
     ];
-    for browser_config in chrome_configs {
-      if let Ok(cookies) = chromium_based(browser_config, cookies_path.into(), domains.clone()) {
+    for name in chrome_names {
+      let key_source = ChromiumKeySource::Browser(name.to_string());
+      if let Ok(cookies) = chromium_based(key_source, cookies_path.into(), domains.clone()) {
         return Ok(cookies);
       }
     }
   }
   #[cfg(target_os = "windows")]
   {
-    if let Some(key_path) = key_path {
-      if let Ok(cookies) = chromium_based(
-        PathBuf::from(key_path),
-        cookies_path.into(),
-        domains.clone(),
-      ) {
-        return Ok(cookies);
-      }
+    let key_source = match key_path {
+      Some(key_path) => ChromiumKeySource::LocalState(key_path.into()),
+      None => ChromiumKeySource::Browser("chrome".to_string()),
+    };
+    if let Ok(cookies) = chromium_based(key_source, cookies_path.into(), domains.clone()) {
+      return Ok(cookies);
     }
   }
   // Windows chromium
@@ -498,9 +419,12 @@ pub fn any_browser(
       return Ok(cookies);
     }
   }
-  bail!(
-    "\nNo cookies found.\n\
+  Err(
+    eyre::eyre!(
+      "\nNo cookies found.\n\
     If you're using a Chromium-based browser, please specify the key file \
     and run this program with administrator privileges."
-  );
+    )
+    .into(),
+  )
 }
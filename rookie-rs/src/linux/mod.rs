@@ -1,27 +1,56 @@
+use crate::{
+  common::cancel,
+  keys::{kdewallet_names, KWalletBackend},
+  RookieError,
+};
 use eyre::{anyhow, bail, Result};
-use std::{collections::HashMap, sync::Arc};
-use zbus::{blocking::Connection, zvariant::ObjectPath, zvariant::Value, Message};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use zbus::{blocking::Connection, zvariant::OwnedObjectPath, Message};
+
+mod secret_service;
 
 pub const APP_ID: &str = "rookie";
 
-/// Get password from either kdewallet or libsecret (ubuntu)
-pub fn get_passwords(unix_crypt_name: &str) -> Result<Vec<String>> {
-  // Attempt to get the password from libsecret
+/// Get passwords of the `chrome_libsecret_os_crypt_password` items from libsecret
+///
+/// A locked keyring is unlocked through its prompt, which the user has `prompt_timeout` to answer.
+/// Fails with [`RookieError::KeyringLocked`] only if no password was found because the keyring is locked
+pub fn get_passwords_libsecret(
+  unix_crypt_name: &str,
+  prompt_timeout: Duration,
+) -> Result<Vec<String>> {
+  let connection = Connection::session()?;
+  let session = secret_service::Session::open(&connection)?;
   let mut passwords: Vec<String> = vec![];
+  let mut last_error = None;
   for schema in [
     "chrome_libsecret_os_crypt_password_v2",
     "chrome_libsecret_os_crypt_password_v1",
   ] {
-    if let Ok(libsecret_pass) = get_password_libsecret(schema, unix_crypt_name) {
-      passwords.push(libsecret_pass);
+    match get_password_libsecret(
+      &connection,
+      &session,
+      schema,
+      unix_crypt_name,
+      prompt_timeout,
+    ) {
+      Ok(libsecret_pass) => passwords.push(libsecret_pass),
+      Err(e) => {
+        let locked = matches!(
+          e.downcast_ref::<RookieError>(),
+          Some(RookieError::KeyringLocked(_))
+        );
+        if locked || last_error.is_none() {
+          last_error = Some(e);
+        }
+      }
     }
   }
-  // Attempt to get the password from kdewallet
-  if let Ok(password) = get_password_kdewallet(unix_crypt_name) {
-    passwords.push(password);
-  }
 
-  Ok(passwords)
+  match (passwords.is_empty(), last_error) {
+    (true, Some(e)) => Err(e),
+    _ => Ok(passwords),
+  }
 }
 
 fn libsecret_call<T>(connection: &Connection, method: &str, args: T) -> zbus::Result<Arc<Message>>
@@ -37,73 +66,332 @@ where
   )
 }
 
-fn kwallet_call<T>(connection: &Connection, method: &str, args: T) -> zbus::Result<Arc<Message>>
+fn kwallet_call<T>(
+  connection: &Connection,
+  backend: KWalletBackend,
+  method: &str,
+  args: T,
+) -> zbus::Result<Arc<Message>>
 where
   T: serde::ser::Serialize + zvariant::DynamicType,
 {
   connection.call_method(
-    Some("org.kde.kwalletd5"),
-    "/modules/kwalletd5",
+    Some(backend.service()),
+    backend.path(),
     Some("org.kde.KWallet"),
     method,
     &args,
   )
 }
 
-fn get_password_libsecret(schema: &str, crypt_name: &str) -> Result<String> {
-  let connection = Connection::session()?;
-  let mut content = HashMap::<&str, &str>::new();
-  content.insert("xdg:schema", schema);
-  content.insert("application", crypt_name);
-  let m = libsecret_call(&connection, "SearchItems", &content)?;
-  let (reply_paths, _): (Vec<ObjectPath>, Vec<ObjectPath>) = m.body()?;
-  let path = reply_paths.first().ok_or(anyhow!("search items empty"))?;
+fn dbus_call<T>(connection: &Connection, method: &str, args: T) -> zbus::Result<Arc<Message>>
+where
+  T: serde::ser::Serialize + zvariant::DynamicType,
+{
+  connection.call_method(
+    Some("org.freedesktop.DBus"),
+    "/org/freedesktop/DBus",
+    Some("org.freedesktop.DBus"),
+    method,
+    &args,
+  )
+}
 
-  let m = libsecret_call(&connection, "Unlock", vec![path])?;
-  let reply: (Vec<ObjectPath>, ObjectPath) = m.body()?;
-  let object_path = reply.0.first().ok_or(anyhow!("Can't unlock"))?;
+/// Returns the KWallet daemon of the session, running ones first, then the ones D-Bus can start
+pub fn kwallet_backend(connection: &Connection) -> Option<KWalletBackend> {
+  let running = KWalletBackend::ALL.into_iter().find(|backend| {
+    dbus_call(connection, "NameHasOwner", backend.service())
+      .and_then(|m| m.body::<bool>())
+      .unwrap_or(false)
+  });
+  running.or_else(|| {
+    let activatable: Vec<String> = dbus_call(connection, "ListActivatableNames", ())
+      .and_then(|m| m.body())
+      .unwrap_or_default();
+    KWalletBackend::ALL
+      .into_iter()
+      .find(|backend| activatable.iter().any(|name| name == backend.service()))
+  })
+}
 
+fn get_password_libsecret(
+  connection: &Connection,
+  session: &secret_service::Session,
+  schema: &str,
+  crypt_name: &str,
+  prompt_timeout: Duration,
+) -> Result<String> {
   let mut content = HashMap::<&str, &str>::new();
-  content.insert("plain", "");
-  let m = libsecret_call(&connection, "OpenSession", &("plain", Value::new("")))?;
-
-  let reply: (Value, ObjectPath) = m.body()?;
-  let session = reply.1;
+  content.insert("xdg:schema", schema);
+  content.insert("application", crypt_name);
+  let m = libsecret_call(connection, "SearchItems", &content)?;
+  let (unlocked_paths, locked_paths): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = m.body()?;
+  let object_path = match (unlocked_paths.first(), locked_paths.first()) {
+    (Some(path), _) => path.clone(),
+    (None, Some(path)) => {
+      let m = libsecret_call(connection, "Unlock", vec![path])?;
+      let (mut unlocked, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) = m.body()?;
+      if unlocked.is_empty() && prompt.as_str() != "/" {
+        log::debug!(
+          "Prompting to unlock the Secret Service item of {}",
+          crypt_name
+        );
+        unlocked = secret_service::prompt(connection, &prompt, prompt_timeout)?;
+      }
+      unlocked
+        .into_iter()
+        .next()
+        .ok_or(RookieError::KeyringLocked(format!(
+          "Secret Service item of {} is locked",
+          crypt_name
+        )))?
+    }
+    (None, None) => bail!("search items empty"),
+  };
 
-  let m = libsecret_call(&connection, "GetSecrets", &(vec![object_path], session))?;
-  type Response<'a> = (ObjectPath<'a>, Vec<u8>, Vec<u8>, String);
-  let reply: HashMap<ObjectPath, Response> = m.body()?;
-  let inner = reply.get(object_path).ok_or(anyhow!("Can't get secrets"))?;
-  let secret = &inner.2;
+  let m = libsecret_call(
+    connection,
+    "GetSecrets",
+    &(vec![&object_path], &session.path),
+  )?;
+  type Response = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+  let reply: HashMap<OwnedObjectPath, Response> = m.body()?;
+  let (_, parameters, value, _) = reply
+    .get(&object_path)
+    .ok_or(anyhow!("Can't get secrets"))?;
+  let secret = session.decrypt(parameters, value)?;
 
-  Ok(String::from_utf8(secret.clone())?)
+  Ok(String::from_utf8(secret)?)
 }
 
-fn get_password_kdewallet(crypt_name: &str) -> Result<String> {
+/// Get password of the `<Browser> Safe Storage` entry from kdewallet, along with the daemon which
+/// holds it
+pub fn get_password_kdewallet(crypt_name: &str) -> Result<(String, KWalletBackend)> {
   let connection = Connection::session()?;
-  let folder = format!("{} Keys", capitalize(crypt_name));
-  let key = format!("{} Safe Storage", capitalize(crypt_name));
+  kdewallet_password(&connection, crypt_name)
+}
 
-  let m = kwallet_call(&connection, "networkWallet", ())?;
+fn kdewallet_password(
+  connection: &Connection,
+  crypt_name: &str,
+) -> Result<(String, KWalletBackend)> {
+  let backend = kwallet_backend(connection).ok_or(anyhow!("KWallet isn't running"))?;
+  log::debug!("Using {}", backend);
+
+  let m = kwallet_call(connection, backend, "networkWallet", ())?;
   let network_wallet: String = m.body()?;
 
-  let m = kwallet_call(&connection, "open", (network_wallet.clone(), 0_i64, APP_ID))?;
+  let m = kwallet_call(
+    connection,
+    backend,
+    "open",
+    (network_wallet.clone(), 0_i64, APP_ID),
+  )?;
   let handle: i32 = m.body()?;
-  let m = kwallet_call(&connection, "readPassword", (handle, folder, key, APP_ID))?;
-  let password: String = m.body()?;
-  let m = kwallet_call(&connection, "close", (network_wallet, false))?;
-  let close_ok: i32 = m.body()?;
-  if close_ok != 1 {
-    bail!("Close failed");
+  if handle < 0 {
+    return Err(
+      RookieError::KeyringLocked(format!(
+        "{} refused to open wallet {}",
+        backend, network_wallet
+      ))
+      .into(),
+    );
+  }
+  // The unlock dialog was answered past the deadline, only release the handle
+  let password = match cancel::is_abandoned() {
+    true => Err(anyhow!("Gave up waiting for wallet {}", network_wallet)),
+    false => read_kdewallet_password(connection, backend, handle, crypt_name),
+  };
+  // Only release our handle, the wallet stays open for other applications
+  let closed = kwallet_call(connection, backend, "close", (handle, false, APP_ID));
+  if let Err(e) = closed {
+    log::debug!("Can't close wallet {}: {}", network_wallet, e);
   }
 
-  Ok(password)
+  Ok((password?, backend))
 }
 
-pub fn capitalize(s: &str) -> String {
-  let mut c = s.chars();
-  match c.next() {
-    None => String::new(),
-    Some(f) => f.to_uppercase().collect::<String>() + c.as_str(),
+fn read_kdewallet_password(
+  connection: &Connection,
+  backend: KWalletBackend,
+  handle: i32,
+  crypt_name: &str,
+) -> Result<String> {
+  for name in kdewallet_names(crypt_name) {
+    let folder = format!("{} Keys", name);
+    let key = format!("{} Safe Storage", name);
+    let m = kwallet_call(
+      connection,
+      backend,
+      "hasEntry",
+      (handle, &folder, &key, APP_ID),
+    )?;
+    if !m.body::<bool>()? {
+      continue;
+    }
+    let m = kwallet_call(
+      connection,
+      backend,
+      "readPassword",
+      (handle, &folder, &key, APP_ID),
+    )?;
+    let password: String = m.body()?;
+    if !password.is_empty() {
+      log::debug!("Found {} in {}", key, folder);
+      return Ok(password);
+    }
+  }
+  bail!("No Safe Storage entry of {} in {}", crypt_name, backend)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+  };
+  use zbus::{blocking::ConnectionBuilder, dbus_interface};
+
+  const BUS_CONFIG: &str = r#"<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#;
+
+  /// Private session bus, stopped when dropped
+  struct Bus {
+    daemon: Child,
+    address: String,
+  }
+
+  impl Bus {
+    /// Starts a bus, failing when `dbus-daemon` isn't installed (package `dbus`)
+    fn start() -> Self {
+      static BUSES: AtomicUsize = AtomicUsize::new(0);
+      // Without service directories, so that no real daemon is activated
+      let config = std::env::temp_dir().join(format!(
+        "rookie-test-bus-{}-{}.conf",
+        std::process::id(),
+        BUSES.fetch_add(1, Ordering::SeqCst)
+      ));
+      std::fs::write(&config, BUS_CONFIG).unwrap();
+      let mut daemon = Command::new("dbus-daemon")
+        .arg(format!("--config-file={}", config.display()))
+        .args(["--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Can't start dbus-daemon, is it installed? {}", e));
+      let mut address = String::new();
+      BufReader::new(daemon.stdout.take().unwrap())
+        .read_line(&mut address)
+        .expect("dbus-daemon didn't print its address");
+      let _ = std::fs::remove_file(config);
+      Self {
+        daemon,
+        address: address.trim().to_string(),
+      }
+    }
+
+    fn connect(&self) -> ConnectionBuilder<'static> {
+      ConnectionBuilder::address(self.address.as_str()).unwrap()
+    }
+
+    /// Serves a wallet as the daemon of a backend, until the connection is dropped
+    fn serve(&self, backend: KWalletBackend) -> Connection {
+      self
+        .connect()
+        .name(backend.service())
+        .unwrap()
+        .serve_at(backend.path(), Wallet { backend })
+        .unwrap()
+        .build()
+        .unwrap()
+    }
+  }
+
+  impl Drop for Bus {
+    fn drop(&mut self) {
+      let _ = self.daemon.kill();
+      let _ = self.daemon.wait();
+    }
+  }
+
+  /// Wallet holding the password of Chrome in `Chrome Keys`, named after its backend
+  struct Wallet {
+    backend: KWalletBackend,
+  }
+
+  #[dbus_interface(name = "org.kde.KWallet")]
+  impl Wallet {
+    #[dbus_interface(name = "networkWallet")]
+    fn network_wallet(&self) -> String {
+      "kdewallet".to_string()
+    }
+
+    #[dbus_interface(name = "open")]
+    fn open(&self, _wallet: String, _window_id: i64, _app_id: String) -> i32 {
+      1
+    }
+
+    #[dbus_interface(name = "hasEntry")]
+    fn has_entry(&self, _handle: i32, folder: String, key: String, _app_id: String) -> bool {
+      folder == "Chrome Keys" && key == "Chrome Safe Storage"
+    }
+
+    #[dbus_interface(name = "readPassword")]
+    fn read_password(
+      &self,
+      _handle: i32,
+      _folder: String,
+      _key: String,
+      _app_id: String,
+    ) -> String {
+      format!("secret of {}", self.backend)
+    }
+
+    #[dbus_interface(name = "close")]
+    fn close(&self, _handle: i32, _force: bool, _app_id: String) -> i32 {
+      0
+    }
+  }
+
+  #[test]
+  fn reads_password_from_each_backend() {
+    for backend in KWalletBackend::ALL {
+      let bus = Bus::start();
+      let _wallet = bus.serve(backend);
+      let connection = bus.connect().build().unwrap();
+      assert_eq!(kwallet_backend(&connection), Some(backend));
+      // Chromium builds may keep the password in `Chrome Keys`
+      let (password, used) = kdewallet_password(&connection, "chromium").unwrap();
+      assert_eq!(password, format!("secret of {}", backend));
+      assert_eq!(used, backend);
+    }
+  }
+
+  #[test]
+  fn prefers_kwalletd6() {
+    let bus = Bus::start();
+    let _kwalletd5 = bus.serve(KWalletBackend::KWalletd5);
+    let _kwalletd6 = bus.serve(KWalletBackend::KWalletd6);
+    let connection = bus.connect().build().unwrap();
+    let (password, used) = kdewallet_password(&connection, "chrome").unwrap();
+    assert_eq!(used, KWalletBackend::KWalletd6);
+    assert_eq!(password, "secret of org.kde.kwalletd6");
+  }
+
+  #[test]
+  fn fails_without_kwallet() {
+    let bus = Bus::start();
+    let connection = bus.connect().build().unwrap();
+    assert_eq!(kwallet_backend(&connection), None);
+    assert!(kdewallet_password(&connection, "chrome").is_err());
   }
 }
@@ -0,0 +1,181 @@
+use super::libsecret_call;
+use crate::{
+  common::cancel::{self, POLL_INTERVAL},
+  RookieError,
+};
+use eyre::{anyhow, bail, Result};
+use hkdf::Hkdf;
+use num_bigint::BigUint;
+use rand::RngCore;
+use sha2::Sha256;
+use std::{
+  sync::mpsc::{self, RecvTimeoutError},
+  thread,
+  time::{Duration, Instant},
+};
+use zbus::{
+  blocking::{Connection, Proxy},
+  zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+const DH_ALGORITHM: &str = "dh-ietf1024-sha256-aes128-cbc-pkcs7";
+
+/// Second Oakley group of RFC 2409, used by the Secret Service `dh-ietf1024` algorithm
+const DH_PRIME: [u8; 128] = [
+  0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xC9, 0x0F, 0xDA, 0xA2, 0x21, 0x68, 0xC2, 0x34,
+  0xC4, 0xC6, 0x62, 0x8B, 0x80, 0xDC, 0x1C, 0xD1, 0x29, 0x02, 0x4E, 0x08, 0x8A, 0x67, 0xCC, 0x74,
+  0x02, 0x0B, 0xBE, 0xA6, 0x3B, 0x13, 0x9B, 0x22, 0x51, 0x4A, 0x08, 0x79, 0x8E, 0x34, 0x04, 0xDD,
+  0xEF, 0x95, 0x19, 0xB3, 0xCD, 0x3A, 0x43, 0x1B, 0x30, 0x2B, 0x0A, 0x6D, 0xF2, 0x5F, 0x14, 0x37,
+  0x4F, 0xE1, 0x35, 0x6D, 0x6D, 0x51, 0xC2, 0x45, 0xE4, 0x85, 0xB5, 0x76, 0x62, 0x5E, 0x7E, 0xC6,
+  0xF4, 0x4C, 0x42, 0xE9, 0xA6, 0x37, 0xED, 0x6B, 0x0B, 0xFF, 0x5C, 0xB6, 0xF4, 0x06, 0xB7, 0xED,
+  0xEE, 0x38, 0x6B, 0xFB, 0x5A, 0x89, 0x9F, 0xA5, 0xAE, 0x9F, 0x24, 0x11, 0x7C, 0x4B, 0x1F, 0xE6,
+  0x49, 0x28, 0x66, 0x51, 0xEC, 0xE6, 0x53, 0x81, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+/// Secret Service session, secrets are encrypted with its AES key unless it's a `plain` session
+pub struct Session {
+  pub path: OwnedObjectPath,
+  key: Option<[u8; 16]>,
+}
+
+impl Session {
+  /// Opens an encrypted session, or a `plain` one when the service doesn't support encryption
+  pub fn open(connection: &Connection) -> Result<Self> {
+    match Self::open_dh(connection) {
+      Ok(session) => Ok(session),
+      Err(e) => {
+        log::debug!("Using a plain session, {} failed: {}", DH_ALGORITHM, e);
+        let m = libsecret_call(connection, "OpenSession", &("plain", Value::new("")))?;
+        let (_, path): (OwnedValue, OwnedObjectPath) = m.body()?;
+        Ok(Self { path, key: None })
+      }
+    }
+  }
+
+  fn open_dh(connection: &Connection) -> Result<Self> {
+    let prime = BigUint::from_bytes_be(&DH_PRIME);
+    let mut private_key = [0u8; 128];
+    rand::thread_rng().fill_bytes(&mut private_key);
+    let private_key = BigUint::from_bytes_be(&private_key);
+    let public_key = BigUint::from(2u32).modpow(&private_key, &prime);
+
+    let m = libsecret_call(
+      connection,
+      "OpenSession",
+      &(DH_ALGORITHM, Value::new(public_key.to_bytes_be())),
+    )?;
+    let (output, path): (OwnedValue, OwnedObjectPath) = m.body()?;
+    let server_key = match &*output {
+      Value::Array(array) => array
+        .iter()
+        .map(|byte| match byte {
+          Value::U8(byte) => Ok(*byte),
+          _ => Err(anyhow!("Invalid server public key")),
+        })
+        .collect::<Result<Vec<u8>>>()?,
+      _ => bail!("Invalid server public key"),
+    };
+
+    let shared_secret = BigUint::from_bytes_be(&server_key)
+      .modpow(&private_key, &prime)
+      .to_bytes_be();
+    // The secret is padded to the size of the prime
+    let mut input_key = vec![0u8; DH_PRIME.len().saturating_sub(shared_secret.len())];
+    input_key.extend(shared_secret);
+    let mut key = [0u8; 16];
+    Hkdf::<Sha256>::new(None, &input_key)
+      .expand(&[], &mut key)
+      .map_err(|e| anyhow!("Can't derive session key: {}", e))?;
+    Ok(Self {
+      path,
+      key: Some(key),
+    })
+  }
+
+  /// Returns the secret of a `GetSecrets` reply
+  pub fn decrypt(&self, parameters: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+    use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+
+    type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+    let Some(key) = self.key else {
+      return Ok(value.to_vec());
+    };
+    let iv: [u8; 16] = parameters
+      .try_into()
+      .map_err(|_| anyhow!("Invalid secret parameters"))?;
+    let cipher = Aes128CbcDec::new(&key.into(), &iv.into());
+    let mut value = value.to_vec();
+    let plaintext = cipher
+      .decrypt_padded_mut::<Pkcs7>(&mut value)
+      .map_err(|_| anyhow!("Can't decrypt secret"))?;
+    Ok(plaintext.to_vec())
+  }
+}
+
+/// Shows the unlock prompt and returns the objects it unlocked
+///
+/// Fails with [`RookieError::KeyringLocked`] when the prompt is dismissed or not answered in time.
+/// The prompt is also dismissed when the operation is abandoned by its deadline.
+pub fn prompt(
+  connection: &Connection,
+  prompt: &ObjectPath,
+  timeout: Duration,
+) -> Result<Vec<OwnedObjectPath>> {
+  if timeout.is_zero() {
+    return Err(
+      RookieError::KeyringLocked("Secret Service requires an unlock prompt".into()).into(),
+    );
+  }
+  let proxy = Proxy::new(
+    connection,
+    "org.freedesktop.secrets",
+    prompt.to_owned(),
+    "org.freedesktop.Secret.Prompt",
+  )?;
+  // Subscribe before prompting, the user may answer right away
+  let mut signals = proxy.receive_signal("Completed")?;
+  let (sender, receiver) = mpsc::channel();
+  thread::spawn(move || {
+    let _ = sender.send(signals.next());
+  });
+  proxy.call_method("Prompt", &(""))?;
+
+  let started = Instant::now();
+  let completed = loop {
+    match receiver.recv_timeout(POLL_INTERVAL) {
+      Err(RecvTimeoutError::Timeout) if started.elapsed() < timeout && !cancel::is_abandoned() => {}
+      completed => break completed,
+    }
+  };
+  match completed {
+    Ok(Some(message)) => {
+      let (dismissed, result): (bool, OwnedValue) = message.body()?;
+      if dismissed {
+        return Err(RookieError::KeyringLocked("Unlock prompt was dismissed".into()).into());
+      }
+      Ok(match &*result {
+        Value::Array(array) => array
+          .iter()
+          .filter_map(|path| match path {
+            Value::ObjectPath(path) => Some(path.to_owned().into()),
+            _ => None,
+          })
+          .collect(),
+        _ => vec![],
+      })
+    }
+    Ok(None) => bail!("Secret Service closed the connection"),
+    Err(_) => {
+      // Completes the prompt, which also stops the thread waiting for it
+      let _ = proxy.call_method("Dismiss", &());
+      Err(
+        RookieError::KeyringLocked(format!(
+          "Unlock prompt wasn't answered within {}s",
+          timeout.as_secs()
+        ))
+        .into(),
+      )
+    }
+  }
+}
@@ -0,0 +1,302 @@
+use crate::browser::chromium::{self, ChromiumKeySource, ChromiumPlatform};
+#[cfg(unix)]
+use crate::keys::KeyRequest;
+use crate::RookieError;
+use aes_gcm::{
+  aead::{generic_array::GenericArray, Aead, KeyInit},
+  Aes256Gcm, Key,
+};
+use rand::RngCore;
+use std::path::Path;
+
+/// Size of the nonce prefixing AES-256-GCM ciphertexts
+const NONCE_LENGTH: usize = 12;
+/// IV of AES-128-CBC ciphertexts, 16 spaces
+const CBC_IV: [u8; 16] = [b' '; 16];
+
+/// Prefix of values encrypted by chromium's `os_crypt`, and by Electron's `safeStorage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+  /// AES-256-GCM with the `Local State` key on Windows, AES-128-CBC with the keychain key on macOS
+  /// and with the `peanuts` key on Linux
+  V10,
+  /// AES-128-CBC with the keyring key (Linux)
+  V11,
+  /// AES-256-GCM with the app-bound key (Windows)
+  V20,
+}
+
+impl Version {
+  /// Returns the version of an encrypted value, if it has a known prefix
+  pub fn of(blob: &[u8]) -> Option<Self> {
+    match blob.get(..3)? {
+      b"v10" => Some(Version::V10),
+      b"v11" => Some(Version::V11),
+      b"v20" => Some(Version::V20),
+      _ => None,
+    }
+  }
+
+  fn prefix(self) -> &'static [u8] {
+    match self {
+      Version::V10 => b"v10",
+      Version::V11 => b"v11",
+      Version::V20 => b"v20",
+    }
+  }
+}
+
+/// Returns the keys of a key source, as [`chromium_based`](crate::chromium_based) reads them
+///
+/// On Linux and macOS the keys of the default passwords follow the ones of the source. The keys of
+/// Windows browsers are read from their `Local State` file, which must be given as a
+/// [`ChromiumKeySource::LocalState`].
+pub fn keys(platform: ChromiumPlatform, source: &ChromiumKeySource) -> crate::Result<Vec<Vec<u8>>> {
+  if platform == ChromiumPlatform::Windows && matches!(source, ChromiumKeySource::Browser(_)) {
+    return Err(
+      eyre::eyre!("Keys of Windows browsers are read from their Local State file").into(),
+    );
+  }
+  let providers = crate::keys::registered();
+  let (keys, keyring_error) = chromium::read_keys(platform, source, &providers, Path::new(""))?;
+  match keyring_error {
+    Some(keyring_error) => Err(keyring_error),
+    None => Ok(keys),
+  }
+}
+
+/// Returns the keys of an application whose `Safe Storage` password is in the keyring (Linux) or
+/// the keychain (macOS), such as an Electron app using `safeStorage`, from the registered key
+/// providers
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{keys::KeyRequest, os_crypt};
+///
+/// let request = KeyRequest {
+///   browser: "my-app".to_string(),
+///   crypt_name: Some("my-app".to_string()),
+///   keychain_service: Some("My App Safe Storage".to_string()),
+///   keychain_account: Some("My App".to_string()),
+/// };
+/// let keys = os_crypt::app_keys(&request).unwrap();
+/// ```
+#[cfg(unix)]
+pub fn app_keys(request: &KeyRequest) -> crate::Result<Vec<Vec<u8>>> {
+  let (keys, keyring_error) = chromium::get_keys(request, &crate::keys::registered())?;
+  match keyring_error {
+    Some(keyring_error) => Err(keyring_error),
+    None => Ok(keys),
+  }
+}
+
+/// Decrypts a value encrypted by `os_crypt`, trying each key
+///
+/// 32 bytes keys decrypt AES-256-GCM values (Windows), 16 bytes keys AES-128-CBC ones (Linux,
+/// macOS). The plaintext is returned as is, values of cookies databases version 24 and later are
+/// still prefixed by the SHA-256 of their domain. As CBC isn't authenticated, the key whose
+/// plaintext is valid UTF-8, once that prefix is skipped, is preferred.
+///
+/// # Examples
+///
+/// ```
+/// use rookie::{os_crypt, ChromiumKeySource, ChromiumPlatform};
+///
+/// let source = ChromiumKeySource::Password("secret".to_string());
+/// let keys = os_crypt::keys(ChromiumPlatform::Linux, &source).unwrap();
+/// let blob = os_crypt::encrypt(b"token", &keys[0], os_crypt::Version::V11).unwrap();
+/// assert_eq!(os_crypt::decrypt(&blob, &keys).unwrap(), b"token");
+/// ```
+pub fn decrypt(blob: &[u8], keys: &[Vec<u8>]) -> crate::Result<Vec<u8>> {
+  if Version::of(blob).is_none() {
+    return Err(RookieError::DecryptionFailed(
+      "value isn't encrypted by os_crypt (v10, v11 or v20)".to_string(),
+    ));
+  }
+  decrypt_gcm(&blob[3..], keys)
+    .or_else(|| decrypt_cbc(&blob[3..], keys, is_text))
+    .ok_or_else(|| RookieError::DecryptionFailed("no key could decrypt the value".to_string()))
+}
+
+/// Encrypts a value the way `os_crypt` does, with AES-256-GCM for 32 bytes keys (`v10` and `v20`)
+/// and AES-128-CBC for 16 bytes keys (`v10` and `v11`)
+pub fn encrypt(plaintext: &[u8], key: &[u8], version: Version) -> crate::Result<Vec<u8>> {
+  let mut blob = version.prefix().to_vec();
+  match (version, key.len()) {
+    (Version::V10 | Version::V20, 32) => {
+      let mut nonce = [0u8; NONCE_LENGTH];
+      rand::thread_rng().fill_bytes(&mut nonce);
+      let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+      let ciphertext = cipher
+        .encrypt(GenericArray::from_slice(&nonce), plaintext)
+        .map_err(|e| eyre::eyre!("Can't encrypt value: {}", e))?;
+      blob.extend(nonce);
+      blob.extend(ciphertext);
+    }
+    (Version::V10 | Version::V11, 16) => {
+      use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+
+      type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+
+      let mut buffer = plaintext.to_vec();
+      buffer.resize(plaintext.len() + CBC_IV.len(), 0);
+      let cipher = Aes128CbcEnc::new(GenericArray::from_slice(key), &CBC_IV.into());
+      let ciphertext = cipher
+        .encrypt_padded_mut::<Pkcs7>(&mut buffer, plaintext.len())
+        .map_err(|e| eyre::eyre!("Can't encrypt value: {}", e))?;
+      blob.extend_from_slice(ciphertext);
+    }
+    (version, len) => {
+      return Err(
+        eyre::eyre!(
+          "{:?} values can't be encrypted with a {} bytes key",
+          version,
+          len
+        )
+        .into(),
+      )
+    }
+  }
+  Ok(blob)
+}
+
+/// Decrypts with AES-256-GCM, the ciphertext is prefixed by a 12 bytes nonce
+pub(crate) fn decrypt_gcm(encrypted_value: &[u8], keys: &[Vec<u8>]) -> Option<Vec<u8>> {
+  if encrypted_value.len() < NONCE_LENGTH {
+    log::debug!("Encrypted value is truncated");
+    return None;
+  }
+  let nonce = GenericArray::from_slice(&encrypted_value[..NONCE_LENGTH]); // 96-bits; unique per message
+  let ciphertext = &encrypted_value[NONCE_LENGTH..];
+
+  for key in keys.iter().filter(|key| key.len() == 32) {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    match cipher.decrypt(nonce, ciphertext) {
+      Ok(plaintext) => return Some(plaintext),
+      // We'll get error anyway if decryption failed
+      Err(e) => log::debug!("Failed to decrypt with a key: {}", e),
+    }
+  }
+  None
+}
+
+/// Decrypts with AES-128-CBC, the IV is made of 16 spaces
+///
+/// A wrong key yields a valid padding for about one value in 256, so the plaintext of the first
+/// key accepted by `is_right` is returned, or else the first valid UTF-8 one, or else the first one.
+pub(crate) fn decrypt_cbc(
+  encrypted_value: &[u8],
+  keys: &[Vec<u8>],
+  is_right: impl Fn(&[u8]) -> bool,
+) -> Option<Vec<u8>> {
+  use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+
+  type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+  let mut plaintexts = vec![];
+  for key in keys.iter().filter(|key| key.len() == 16) {
+    let mut key_array: [u8; 16] = [0; 16];
+    key_array.copy_from_slice(key);
+    let cipher = Aes128CbcDec::new(&key_array.into(), &CBC_IV.into());
+    let mut cloned_encrypted_value: Vec<u8> = encrypted_value.to_vec();
+
+    if let Ok(plaintext) = cipher.decrypt_padded_mut::<Pkcs7>(&mut cloned_encrypted_value) {
+      if is_right(plaintext) {
+        return Some(plaintext.to_vec());
+      }
+      plaintexts.push(plaintext.to_vec());
+    }
+  }
+  let index = plaintexts
+    .iter()
+    .position(|plaintext| std::str::from_utf8(plaintext).is_ok())
+    .unwrap_or(0);
+  (index < plaintexts.len()).then(|| plaintexts.swap_remove(index))
+}
+
+/// Returns true if the plaintext is valid UTF-8, with or without a SHA-256 domain hash prefix
+fn is_text(plaintext: &[u8]) -> bool {
+  std::str::from_utf8(plaintext).is_ok()
+    || plaintext
+      .get(32..)
+      .is_some_and(|value| std::str::from_utf8(value).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cbc_key(byte: u8) -> Vec<u8> {
+    vec![byte; 16]
+  }
+
+  #[test]
+  fn decrypts_cbc() {
+    let keys = [cbc_key(1)];
+    for version in [Version::V10, Version::V11] {
+      let blob = encrypt(b"token", &keys[0], version).unwrap();
+      assert_eq!(Version::of(&blob), Some(version));
+      assert_eq!(decrypt(&blob, &keys).unwrap(), b"token");
+    }
+  }
+
+  #[test]
+  fn decrypts_gcm() {
+    let key: Vec<u8> = (0..32).collect();
+    let blob = encrypt(b"token", &key, Version::V10).unwrap();
+    // Keys of other sizes are skipped
+    assert_eq!(decrypt(&blob, &[cbc_key(1), key]).unwrap(), b"token");
+  }
+
+  #[test]
+  fn rejects_wrong_key() {
+    let blob = encrypt(b"token", &(0..32).collect::<Vec<u8>>(), Version::V10).unwrap();
+    let result = decrypt(&blob, &[vec![0; 32]]);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+
+    let blob = encrypt(b"token", &cbc_key(1), Version::V11).unwrap();
+    let result = decrypt(&blob, &[cbc_key(2)]);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+  }
+
+  #[test]
+  fn skips_wrong_key_with_valid_padding() {
+    let (wrong, right) = (cbc_key(2), cbc_key(1));
+    let has_valid_padding =
+      |blob: &Vec<u8>| decrypt_cbc(&blob[3..], std::slice::from_ref(&wrong), |_| false).is_some();
+    // About one value in 256 has a valid padding with the wrong key
+    let (value, blob) = (0..)
+      .map(|i| format!("token {}", i))
+      .map(|value| {
+        let blob = encrypt(value.as_bytes(), &right, Version::V11).unwrap();
+        (value, blob)
+      })
+      .find(|(_, blob)| has_valid_padding(blob))
+      .unwrap();
+    assert_eq!(decrypt(&blob, &[wrong, right]).unwrap(), value.as_bytes());
+  }
+
+  #[test]
+  fn rejects_bad_padding() {
+    let key = cbc_key(1);
+    // Without its padding block, the last byte of the value is taken for the padding
+    let blob = encrypt(b"0123456789abcde\0", &key, Version::V10).unwrap();
+    assert_eq!(blob.len(), 3 + 32);
+    let result = decrypt(&blob[..3 + 16], &[key]);
+    assert!(matches!(result, Err(RookieError::DecryptionFailed(_))));
+  }
+
+  #[test]
+  fn rejects_truncated_blob() {
+    let keys = [cbc_key(1), (0..32).collect()];
+    let blob = encrypt(b"token", &keys[0], Version::V11).unwrap();
+    for len in 0..blob.len() {
+      assert!(decrypt(&blob[..len], &keys).is_err(), "{} bytes", len);
+    }
+    let blob = encrypt(b"token", &keys[1], Version::V10).unwrap();
+    for len in 0..blob.len() {
+      assert!(decrypt(&blob[..len], &keys).is_err(), "{} bytes", len);
+    }
+  }
+}
@@ -0,0 +1,225 @@
+use crate::{
+  browser::{chromium, mozilla},
+  common::paths,
+  config::{find_browser_config, Browser},
+  query::{browser_kind, default_browsers, BrowserKind},
+  Result, RookieError,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// A browser profile with a cookies file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Profile {
+  /// Browser config name
+  pub browser: String,
+  /// Release channel (`stable`, `beta`, ...)
+  pub channel: String,
+  /// Profile directory name (`Default`, `Profile 2`, `abcd.default-release`, ...)
+  pub directory: String,
+  /// Name shown by the browser
+  pub name: Option<String>,
+  /// E-mail of the account signed in the profile
+  pub email: Option<String>,
+  /// Whether the browser opens this profile by default
+  pub is_default: bool,
+  pub db_path: PathBuf,
+  /// `Local State` file of chromium based browsers
+  pub key_path: Option<PathBuf>,
+}
+
+impl Profile {
+  /// Returns a name to show to users, such as `Work (alice@corp.com)`
+  pub fn display_name(&self) -> String {
+    let name = self.name.as_ref().unwrap_or(&self.directory);
+    match &self.email {
+      Some(email) => format!("{} ({})", name, email),
+      None => name.to_string(),
+    }
+  }
+}
+
+/// Selects a profile by its directory, display name or index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileSelector {
+  /// Directory name (`Profile 3`) or name shown by the browser (`Work`), ignoring case
+  Name(String),
+  /// Position in the profiles of every channel of the browser, as returned by
+  /// [`browser_profiles`], whatever the channels of the query
+  Index(usize),
+}
+
+impl ProfileSelector {
+  /// Parses a selector given by users, numbers are taken as index
+  pub fn parse(selector: &str) -> Self {
+    match selector.parse::<usize>() {
+      Ok(index) => ProfileSelector::Index(index),
+      Err(_) => ProfileSelector::Name(selector.to_string()),
+    }
+  }
+
+  /// Returns true if the profile at `index` of its browser profiles is selected
+  pub fn matches(&self, index: usize, profile: &Profile) -> bool {
+    match self {
+      ProfileSelector::Name(name) => {
+        profile.directory.eq_ignore_ascii_case(name)
+          || profile
+            .name
+            .as_ref()
+            .is_some_and(|profile_name| profile_name.eq_ignore_ascii_case(name))
+          || profile.display_name().eq_ignore_ascii_case(name)
+      }
+      ProfileSelector::Index(wanted) => *wanted == index,
+    }
+  }
+}
+
+impl From<&str> for ProfileSelector {
+  fn from(name: &str) -> Self {
+    ProfileSelector::Name(name.to_string())
+  }
+}
+
+impl From<String> for ProfileSelector {
+  fn from(name: String) -> Self {
+    ProfileSelector::Name(name)
+  }
+}
+
+impl From<usize> for ProfileSelector {
+  fn from(index: usize) -> Self {
+    ProfileSelector::Index(index)
+  }
+}
+
+/// Returns the profiles of every installed browser
+///
+/// # Examples
+///
+/// ```
+/// for profile in rookie::list_profiles() {
+///   println!("{}: {}", profile.browser, profile.display_name());
+/// }
+/// ```
+pub fn list_profiles() -> Vec<Profile> {
+  default_browsers()
+    .into_iter()
+    .filter_map(|browser| browser_profiles(browser).ok())
+    .flatten()
+    .collect()
+}
+
+/// Returns the profiles of a browser by its config name
+pub fn browser_profiles(browser: &str) -> Result<Vec<Profile>> {
+  let config =
+    find_browser_config(browser).ok_or(RookieError::UnsupportedBrowser(browser.to_string()))?;
+  find_profiles(browser, config, &[])
+}
+
+/// Returns the profiles of a browser in the wanted channels, or all channels if none given
+pub(crate) fn find_profiles(
+  browser: &str,
+  config: &Browser,
+  channels: &[String],
+) -> Result<Vec<Profile>> {
+  let mut profiles: Vec<Profile> = vec![];
+  for channel in paths::select_channels(config, channels) {
+    let found = match browser_kind(browser) {
+      BrowserKind::Chromium => chromium_profiles(browser, config, &channel)?,
+      BrowserKind::Mozilla => mozilla_profiles(browser, config, &channel)?,
+      #[cfg(target_os = "macos")]
+      BrowserKind::Safari => paths::find_safari_based_paths(config)
+        .map(|db_path| file_profile(browser, &channel, db_path))
+        .into_iter()
+        .collect(),
+      #[cfg(target_os = "windows")]
+      BrowserKind::InternetExplorer => paths::find_ie_based_paths(config)
+        .map(|db_path| file_profile(browser, &channel, db_path))
+        .into_iter()
+        .collect(),
+      #[allow(unreachable_patterns)]
+      _ => vec![],
+    };
+    for profile in found {
+      if !profiles.iter().any(|p| p.db_path == profile.db_path) {
+        profiles.push(profile);
+      }
+    }
+  }
+  Ok(profiles)
+}
+
+/// Returns the only profile of browsers without profiles
+#[cfg(not(target_os = "linux"))]
+fn file_profile(browser: &str, channel: &str, db_path: PathBuf) -> Profile {
+  Profile {
+    browser: browser.to_string(),
+    channel: paths::normalize_channel(channel),
+    directory: paths::profile_dir_name(&db_path).unwrap_or_default(),
+    name: None,
+    email: None,
+    is_default: true,
+    db_path,
+    key_path: None,
+  }
+}
+
+fn chromium_profiles(browser: &str, config: &Browser, channel: &str) -> Result<Vec<Profile>> {
+  let mut profiles = vec![];
+  for (key_path, db_path) in paths::find_all_chrome_based_paths(config, &[channel.to_string()])? {
+    let Some(directory) = paths::profile_dir_name(&db_path) else {
+      continue;
+    };
+    let (mut info_cache, last_used) =
+      chromium::read_profile_info_cache(&key_path).unwrap_or_default();
+    let info = info_cache.remove(&directory);
+    profiles.push(Profile {
+      browser: browser.to_string(),
+      channel: paths::normalize_channel(channel),
+      is_default: directory == last_used.unwrap_or("Default".to_string()),
+      name: info.as_ref().and_then(|info| info.name.clone()),
+      email: info.and_then(|info| info.email),
+      directory,
+      db_path,
+      key_path: Some(key_path),
+    });
+  }
+  Ok(profiles)
+}
+
+fn mozilla_profiles(browser: &str, config: &Browser, channel: &str) -> Result<Vec<Profile>> {
+  let mut profiles = vec![];
+  for db_path in paths::find_all_mozilla_based_paths(config, &[channel.to_string()])? {
+    let (Some(profile_dir), Some(directory)) =
+      (db_path.parent(), paths::profile_dir_name(&db_path))
+    else {
+      continue;
+    };
+    let ini_profile = profiles_ini_entry(profile_dir);
+    profiles.push(Profile {
+      browser: browser.to_string(),
+      channel: paths::normalize_channel(channel),
+      directory,
+      name: ini_profile.as_ref().and_then(|p| p.name.clone()),
+      email: mozilla::read_account_email(profile_dir),
+      is_default: ini_profile.is_some_and(|p| p.is_default),
+      db_path,
+      key_path: None,
+    });
+  }
+  Ok(profiles)
+}
+
+/// Returns the profiles.ini entry of a profile directory, profiles.ini is next to it or its parent
+fn profiles_ini_entry(profile_dir: &Path) -> Option<mozilla::IniProfile> {
+  let base_dir = profile_dir
+    .ancestors()
+    .skip(1)
+    .take(2)
+    .find(|dir| dir.join("profiles.ini").exists())?;
+  let profile_dir = profile_dir.canonicalize().ok()?;
+  mozilla::read_profiles_ini(base_dir)
+    .ok()?
+    .into_iter()
+    .find(|p| p.path.canonicalize().is_ok_and(|path| path == profile_dir))
+}
@@ -0,0 +1,529 @@
+use crate::{
+  browser::{
+    chromium::{chromium_based_partial, ChromiumKeySource, ChromiumPlatform},
+    mozilla::firefox_based_filtered,
+  },
+  common::{
+    cancel::{CancelHandle, Deadline},
+    date,
+    domain::{DomainFilter, DomainMatch},
+    enums::{Cookie, DecryptionMode},
+    request::{cookies_for_url, RequestContext},
+  },
+  config::{find_browser_config, Browser},
+  keys::{self, KeyProviders},
+  profiles::{self, Profile, ProfileSelector},
+  report::{BrowserReport, LoadStatus},
+  Result, RookieError,
+};
+use std::{path::PathBuf, time::Duration};
+use url::Url;
+
+#[cfg(target_os = "windows")]
+use crate::browser::internet_explorer::internet_explorer_based_filtered;
+#[cfg(target_os = "macos")]
+use crate::browser::safari::safari_based_filtered;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BrowserKind {
+  Chromium,
+  Mozilla,
+  Safari,
+  InternetExplorer,
+}
+
+pub(crate) fn browser_kind(name: &str) -> BrowserKind {
+  match name {
+    "firefox" | "librewolf" | "zen" | "cachy" => BrowserKind::Mozilla,
+    "safari" => BrowserKind::Safari,
+    "ie" => BrowserKind::InternetExplorer,
+    _ => BrowserKind::Chromium,
+  }
+}
+
+/// A cookies database of a browser profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieFile {
+  /// Browser config name
+  pub browser: String,
+  /// Profile directory name
+  pub profile: Option<String>,
+  pub db_path: PathBuf,
+  /// `Local State` file of chromium based browsers
+  pub key_path: Option<PathBuf>,
+}
+
+impl From<Profile> for CookieFile {
+  fn from(profile: Profile) -> Self {
+    Self {
+      browser: profile.browser,
+      profile: Some(profile.directory),
+      db_path: profile.db_path,
+      key_path: profile.key_path,
+    }
+  }
+}
+
+/// Browsers queried when none is selected, same as `rookie::load`
+pub(crate) fn default_browsers() -> Vec<&'static str> {
+  #[allow(unused_mut)]
+  let mut browsers = vec![
+    "firefox",
+    "zen",
+    "librewolf",
+    "opera",
+    "edge",
+    "chromium",
+    "brave",
+    "vivaldi",
+    "arc",
+  ];
+  #[cfg(target_os = "windows")]
+  browsers.extend(["chrome", "ie", "opera_gx"]);
+  #[cfg(target_os = "linux")]
+  browsers.extend(["chrome", "cachy"]);
+  #[cfg(target_os = "macos")]
+  browsers.extend(["chrome", "opera_gx", "safari"]);
+  browsers
+}
+
+/// Builder for extracting cookies with filters
+///
+/// # Examples
+///
+/// ```no_run
+/// use rookie::{common::domain::DomainMatch, CookieQuery};
+///
+/// // Chrome beta, profile 2, only github.com, unexpired
+/// let cookies = CookieQuery::new()
+///   .browser("chrome")
+///   .channel("beta")
+///   .profile("Profile 2")
+///   .domain("github.com")
+///   .domain_match(DomainMatch::Exact)
+///   .include_expired(false)
+///   .run()
+///   .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CookieQuery {
+  browsers: Vec<String>,
+  profiles: Vec<ProfileSelector>,
+  all_profiles: bool,
+  channels: Vec<String>,
+  domains: Option<Vec<String>>,
+  domain_match: DomainMatch,
+  decryption_mode: DecryptionMode,
+  key_providers: Option<KeyProviders>,
+  deadline: Deadline,
+  names: Option<Vec<String>>,
+  include_session: bool,
+  include_expired: bool,
+}
+
+impl Default for CookieQuery {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl CookieQuery {
+  /// Creates a query over every supported browser without any filter
+  pub fn new() -> Self {
+    Self {
+      browsers: vec![],
+      profiles: vec![],
+      all_profiles: false,
+      channels: vec![],
+      domains: None,
+      domain_match: DomainMatch::default(),
+      decryption_mode: DecryptionMode::default(),
+      key_providers: None,
+      deadline: Deadline::default(),
+      names: None,
+      include_session: true,
+      include_expired: true,
+    }
+  }
+
+  /// Adds a browser by its config name (`chrome`, `firefox`, `opera_gx`, ...)
+  pub fn browser(mut self, name: impl Into<String>) -> Self {
+    let name = name.into();
+    let name = match name.as_str() {
+      "internet_explorer" => "ie".to_string(),
+      _ => name,
+    };
+    self.browsers.push(name);
+    self
+  }
+
+  /// Adds several browsers, see [`CookieQuery::browser`]
+  pub fn browsers<I, S>(self, names: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    names
+      .into_iter()
+      .fold(self, |query, name| query.browser(name))
+  }
+
+  /// Adds a profile by directory name (`Profile 2`, `abcd.default-release`, ...), display name
+  /// (`Work`) or index, see [`ProfileSelector`]
+  ///
+  /// Without profiles the first profile found is used, unless [`CookieQuery::all_profiles`] is set.
+  pub fn profile(mut self, selector: impl Into<ProfileSelector>) -> Self {
+    self.profiles.push(selector.into());
+    self
+  }
+
+  /// Whether to extract from every profile of each browser instead of the first one (default false)
+  pub fn all_profiles(mut self, all: bool) -> Self {
+    self.all_profiles = all;
+    self
+  }
+
+  /// Adds a release channel (`stable`, `beta`, `dev`, `nightly`, ...)
+  ///
+  /// Without channels every channel is searched.
+  pub fn channel(mut self, name: impl Into<String>) -> Self {
+    self.channels.push(name.into());
+    self
+  }
+
+  /// Adds a domain filter
+  pub fn domain(mut self, domain: impl Into<String>) -> Self {
+    self
+      .domains
+      .get_or_insert_with(Vec::new)
+      .push(domain.into());
+    self
+  }
+
+  /// Adds several domain filters
+  pub fn domains<I, S>(self, domains: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    domains
+      .into_iter()
+      .fold(self, |query, domain| query.domain(domain))
+  }
+
+  /// Sets how cookie domains are compared against domain filters
+  pub fn domain_match(mut self, domain_match: DomainMatch) -> Self {
+    self.domain_match = domain_match;
+    self
+  }
+
+  /// Sets how values which can't be decrypted are handled (default [`DecryptionMode::Strict`])
+  ///
+  /// With [`DecryptionMode::Lenient`] a profile with corrupted values still returns its cookies,
+  /// each one tells its outcome in [`Cookie::decryption`].
+  pub fn decryption_mode(mut self, mode: DecryptionMode) -> Self {
+    self.decryption_mode = mode;
+    self
+  }
+
+  /// Sets where the passwords of chromium browsers come from on Linux and macOS (default
+  /// [`keys::registered`])
+  pub fn key_providers(mut self, providers: KeyProviders) -> Self {
+    self.key_providers = Some(providers);
+    self
+  }
+
+  /// Sets how long reading the keys from the keyring, and unlocking the cookies files on Windows,
+  /// may take for the whole query before failing with [`RookieError::Timeout`]
+  ///
+  /// Overrides the timeout of the key providers.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.deadline.timeout = Some(timeout);
+    self
+  }
+
+  /// Sets a handle cancelling the query from another thread, which fails with
+  /// [`RookieError::Cancelled`]
+  pub fn cancel_handle(mut self, cancel: CancelHandle) -> Self {
+    self.deadline.cancel = Some(cancel);
+    self
+  }
+
+  /// Adds a cookie name filter
+  pub fn name(mut self, name: impl Into<String>) -> Self {
+    self.names.get_or_insert_with(Vec::new).push(name.into());
+    self
+  }
+
+  /// Adds several cookie name filters
+  pub fn names<I, S>(self, names: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    names.into_iter().fold(self, |query, name| query.name(name))
+  }
+
+  /// Whether to include cookies without expiration date (default true)
+  pub fn include_session(mut self, include: bool) -> Self {
+    self.include_session = include;
+    self
+  }
+
+  /// Whether to include cookies which already expired (default true)
+  pub fn include_expired(mut self, include: bool) -> Self {
+    self.include_expired = include;
+    self
+  }
+
+  /// Extracts the cookies
+  ///
+  /// When a single browser is selected its error is returned if none of its profiles could be
+  /// extracted, otherwise browsers which fail are skipped. A timeout or a cancellation is always
+  /// returned.
+  pub fn run(&self) -> Result<Vec<Cookie>> {
+    let single = self.selected_browsers().len() == 1;
+    let mut cookies = vec![];
+    let mut extracted = false;
+    let mut error = None;
+    for report in self.run_with_report() {
+      match report.status {
+        LoadStatus::NotInstalled => {
+          error.get_or_insert(RookieError::BrowserNotInstalled(report.browser));
+        }
+        // The query was given up, whatever the other browsers returned
+        LoadStatus::Failed(e @ (RookieError::Timeout { .. } | RookieError::Cancelled(_))) => {
+          return Err(e)
+        }
+        LoadStatus::Failed(e) => {
+          log::debug!("Skipping {}: {}", report.browser, e);
+          error.get_or_insert(e);
+        }
+        LoadStatus::Succeeded | LoadStatus::PartiallyDecrypted { .. } => {
+          extracted = true;
+          cookies.extend(report.cookies)
+        }
+      }
+    }
+    match error {
+      Some(error) if single && !extracted => Err(error),
+      _ => Ok(cookies),
+    }
+  }
+
+  /// Extracts the cookies a browser would send to the URL, see [`cookies_for_url`]
+  ///
+  /// Domain filters of the query are replaced by the URL host.
+  pub fn run_for_url(&self, url: &str, context: RequestContext) -> Result<Vec<Cookie>> {
+    // Checked before reading any browser profile
+    let parsed =
+      Url::parse(url).map_err(|e| eyre::Report::new(e).wrap_err(format!("Invalid URL {}", url)))?;
+    let Some(host) = parsed.host_str().map(String::from) else {
+      // No cookie is sent to URLs without a host
+      return Ok(vec![]);
+    };
+    let mut query = self.clone().domain_match(DomainMatch::Domain);
+    query.domains = Some(vec![host]);
+    let cookies = query.run()?;
+    Ok(
+      cookies_for_url(&cookies, url, context)?
+        .into_iter()
+        .cloned()
+        .collect(),
+    )
+  }
+
+  /// Extracts the cookies and reports the outcome of every browser profile
+  ///
+  /// Reports stop at the profile which timed out or was cancelled.
+  pub fn run_with_report(&self) -> Vec<BrowserReport> {
+    let deadline = self.deadline.start();
+    let mut reports = vec![];
+    for name in self.selected_browsers() {
+      let report = |profile, status| BrowserReport {
+        browser: name.clone(),
+        profile,
+        status,
+        cookies: vec![],
+      };
+      let Some(config) = find_browser_config(&name) else {
+        reports.push(report(
+          None,
+          LoadStatus::Failed(RookieError::UnsupportedBrowser(name.clone())),
+        ));
+        continue;
+      };
+      let kind = browser_kind(&name);
+      let sources = match self.find_files(&name, config) {
+        Ok(sources) => sources,
+        Err(e) => {
+          reports.push(report(None, LoadStatus::Failed(e)));
+          continue;
+        }
+      };
+      if sources.is_empty() {
+        reports.push(report(None, LoadStatus::NotInstalled));
+        continue;
+      }
+
+      for source in sources {
+        let profile = source.profile.clone();
+        let mut source_report = report(profile.clone(), LoadStatus::Succeeded);
+        match self.extract(config, kind, source, &deadline) {
+          Ok((cookies, failed)) => {
+            if failed > 0 {
+              source_report.status = LoadStatus::PartiallyDecrypted { failed };
+            }
+            let now = date::unix_now();
+            source_report.cookies = cookies
+              .into_iter()
+              .filter(|cookie| self.keep(cookie, now))
+              .map(|cookie| Cookie {
+                profile: profile.clone(),
+                ..cookie
+              })
+              .collect();
+          }
+          Err(e) => source_report.status = LoadStatus::Failed(e),
+        }
+        let given_up = matches!(
+          source_report.status,
+          LoadStatus::Failed(RookieError::Timeout { .. } | RookieError::Cancelled(_))
+        );
+        reports.push(source_report);
+        if given_up {
+          return reports;
+        }
+      }
+    }
+    reports
+  }
+
+  fn selected_browsers(&self) -> Vec<String> {
+    if self.browsers.is_empty() {
+      default_browsers().into_iter().map(String::from).collect()
+    } else {
+      self.browsers.clone()
+    }
+  }
+
+  /// Returns the cookies databases which would be extracted from
+  ///
+  /// Browsers which aren't supported or fail to be searched are skipped.
+  pub fn cookie_files(&self) -> Vec<CookieFile> {
+    self
+      .selected_browsers()
+      .iter()
+      .filter_map(|name| {
+        let config = find_browser_config(name)?;
+        self.find_files(name, config).ok()
+      })
+      .flatten()
+      .collect()
+  }
+
+  /// Returns the cookies files of a browser to extract from
+  fn find_files(&self, name: &str, config: &Browser) -> Result<Vec<CookieFile>> {
+    let profiles = profiles::find_profiles(name, config, &self.channels)?;
+    let indexed = self
+      .profiles
+      .iter()
+      .any(|selector| matches!(selector, ProfileSelector::Index(_)));
+    let selected = if indexed && !self.channels.is_empty() {
+      // Indexes are positions in every profile of the browser, as listed by `browser_profiles`
+      let all = profiles::find_profiles(name, config, &[])?;
+      self
+        .select_profiles(all)
+        .into_iter()
+        .filter(|profile| profiles.iter().any(|p| p.db_path == profile.db_path))
+        .collect()
+    } else {
+      self.select_profiles(profiles)
+    };
+    Ok(selected.into_iter().map(CookieFile::from).collect())
+  }
+
+  /// Returns cookies of a source along with the number of values which couldn't be decrypted
+  #[allow(unused_variables)]
+  fn extract(
+    &self,
+    config: &Browser,
+    kind: BrowserKind,
+    source: CookieFile,
+    deadline: &Deadline,
+  ) -> Result<(Vec<Cookie>, usize)> {
+    deadline.check(&format!("Extracting cookies of {}", source.browser))?;
+    let filter = DomainFilter::new(self.domains.clone(), self.domain_match);
+    let db_path = source.db_path;
+    match kind {
+      BrowserKind::Chromium => {
+        #[cfg(target_os = "windows")]
+        let key_source = match source.key_path {
+          Some(key_path) => ChromiumKeySource::LocalState(key_path),
+          None => ChromiumKeySource::Browser(source.browser),
+        };
+        #[cfg(unix)]
+        let key_source = ChromiumKeySource::Browser(source.browser);
+        let platform = ChromiumPlatform::current();
+        let providers = self
+          .key_providers
+          .clone()
+          .unwrap_or_else(keys::registered)
+          .with_deadline(deadline);
+        chromium_based_partial(
+          platform,
+          &key_source,
+          &providers,
+          deadline,
+          db_path,
+          &filter,
+          self.decryption_mode,
+        )
+      }
+      BrowserKind::Mozilla => Ok((firefox_based_filtered(db_path, &filter)?, 0)),
+      #[cfg(target_os = "macos")]
+      BrowserKind::Safari => Ok((safari_based_filtered(db_path, &filter)?, 0)),
+      #[cfg(target_os = "windows")]
+      BrowserKind::InternetExplorer => Ok((
+        internet_explorer_based_filtered(db_path, &filter, deadline)?,
+        0,
+      )),
+      #[allow(unreachable_patterns)]
+      _ => Ok((vec![], 0)),
+    }
+  }
+
+  /// Keeps the wanted profiles, or the first one when none is wanted and not all profiles are
+  fn select_profiles(&self, profiles: Vec<Profile>) -> Vec<Profile> {
+    if self.profiles.is_empty() {
+      let count = if self.all_profiles { profiles.len() } else { 1 };
+      return profiles.into_iter().take(count).collect();
+    }
+    profiles
+      .into_iter()
+      .enumerate()
+      .filter(|(index, profile)| {
+        self
+          .profiles
+          .iter()
+          .any(|selector| selector.matches(*index, profile))
+      })
+      .map(|(_, profile)| profile)
+      .collect()
+  }
+
+  fn keep(&self, cookie: &Cookie, now: u64) -> bool {
+    if let Some(names) = &self.names {
+      if !names.contains(&cookie.name) {
+        return false;
+      }
+    }
+    if !self.include_session && cookie.expires.is_none() {
+      return false;
+    }
+    if !self.include_expired && cookie.expires.is_some_and(|expires| expires < now) {
+      return false;
+    }
+    true
+  }
+}
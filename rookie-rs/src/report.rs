@@ -0,0 +1,25 @@
+use crate::{enums::Cookie, RookieError};
+
+/// Outcome of extracting cookies from a browser profile
+#[derive(Debug)]
+pub enum LoadStatus {
+  /// No cookies file was found
+  NotInstalled,
+  /// Every cookie was extracted
+  Succeeded,
+  /// Some cookie values couldn't be decrypted, see [`Cookie::decryption`]
+  PartiallyDecrypted { failed: usize },
+  /// Nothing could be extracted
+  Failed(RookieError),
+}
+
+/// Cookies extracted from a browser profile along with the outcome
+#[derive(Debug)]
+pub struct BrowserReport {
+  /// Browser config name (`chrome`, `firefox`, ...)
+  pub browser: String,
+  /// Profile directory name, if a cookies file was found
+  pub profile: Option<String>,
+  pub status: LoadStatus,
+  pub cookies: Vec<Cookie>,
+}